@@ -0,0 +1,150 @@
+//! Optional encryption at rest with SQLCipher, compiled in by the `sqlcipher` cargo feature.
+//!
+//! The app never stores the key. It is read from `SPECMAKER_DB_KEY` at startup, so it has to be
+//! supplied the same way on every launch. Encryption adds some CPU cost to every page read and
+//! write, and a lost key cannot be recovered: the database is unreadable without it.
+
+use rusqlite::{ffi, Connection, Result};
+use std::path::Path;
+
+pub const KEY_ENV: &str = "SPECMAKER_DB_KEY";
+
+const ENABLED: bool = cfg!(feature = "sqlcipher");
+
+/// Opens the database at `path`, unlocking it with `key` before anything else reads the file.
+pub fn open(path: &Path, key: Option<&str>) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    apply_key(&conn, key)?;
+    Ok(conn)
+}
+
+fn apply_key(conn: &Connection, key: Option<&str>) -> Result<()> {
+    let Some(key) = key else {
+        return Ok(());
+    };
+    if !ENABLED {
+        return Err(unsupported());
+    }
+
+    conn.pragma_update(None, "key", key)?;
+    // A wrong key only shows up on the first read.
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+}
+
+/// `ATTACH` statement for `alias`, with a `KEY` clause only when there is a key, so plain
+/// SQLite builds never see one.
+pub fn attach_sql(alias: &str, key: Option<&str>) -> String {
+    match key {
+        Some(_) => format!("ATTACH DATABASE ?1 AS {} KEY ?2", alias),
+        None => format!("ATTACH DATABASE ?1 AS {}", alias),
+    }
+}
+
+/// Rewrites the database at `path` encrypted with `key`, or as plaintext when `key` is `None`,
+/// using `sqlcipher_export`. The original, currently open with `old_key`, is only replaced once
+/// the copy is complete, and `conn` is reopened on the new file. On failure the copy is removed
+/// and `conn` stays on the original.
+pub fn rekey(
+    conn: &mut Connection,
+    path: &Path,
+    old_key: Option<&str>,
+    key: Option<&str>,
+) -> Result<(), String> {
+    if path.as_os_str().is_empty() {
+        return Err("An in-memory database cannot be encrypted".to_string());
+    }
+    if !ENABLED {
+        return Err(unsupported().to_string());
+    }
+
+    let target = path.with_extension("rekey.db");
+    let original = path.with_extension("prekey.db");
+    for stale in [&target, &original] {
+        if stale.exists() {
+            std::fs::remove_file(stale).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let rekeyed = export(conn, &target, key)
+        .and_then(|_| super::swap_database_file(conn, path, &target, &original, key, old_key));
+    if let Err(e) = rekeyed {
+        // A failed decrypt would otherwise leave a partial plaintext copy behind.
+        let _ = std::fs::remove_file(&target);
+        return Err(e);
+    }
+
+    std::fs::remove_file(&original).map_err(|e| e.to_string())
+}
+
+fn export(conn: &Connection, target: &Path, key: Option<&str>) -> Result<(), String> {
+    // An empty key makes SQLCipher write the attached copy unencrypted.
+    conn.execute(
+        "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+        (target.to_string_lossy().as_ref(), key.unwrap_or("")),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let exported = conn.query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()));
+    conn.execute("DETACH DATABASE rekeyed", [])
+        .map_err(|e| e.to_string())?;
+    exported.map_err(|e| e.to_string())
+}
+
+fn unsupported() -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_MISUSE),
+        Some(format!(
+            "A database key is configured, but this build does not include encryption support \
+             (enable the `sqlcipher` feature), or unset {}",
+            KEY_ENV
+        )),
+    )
+}
+
+#[cfg(all(test, feature = "sqlcipher"))]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    fn project_names(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT name FROM projects")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn encrypts_and_decrypts_in_place() {
+        let dir = std::env::temp_dir().join(format!("spec-maker-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("spec_maker.db");
+        let db = Database::new(path.clone(), None).unwrap();
+        let mut conn = db.lock();
+        conn.execute(
+            "INSERT INTO projects (id, name, description) VALUES ('p1', 'Tracker', 'Tasks')",
+            [],
+        )
+        .unwrap();
+
+        rekey(&mut conn, &path, None, Some("secret")).unwrap();
+        assert_eq!(project_names(&conn), ["Tracker"]);
+        assert!(open(&path, None)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+            .is_err());
+        assert_eq!(
+            project_names(&open(&path, Some("secret")).unwrap()),
+            ["Tracker"]
+        );
+
+        rekey(&mut conn, &path, Some("secret"), None).unwrap();
+        assert_eq!(project_names(&open(&path, None).unwrap()), ["Tracker"]);
+        assert!(!path.with_extension("rekey.db").exists());
+        assert!(!path.with_extension("prekey.db").exists());
+
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,390 @@
+use super::encryption;
+use super::models::{
+    CompactReport, ForeignKeyViolation, IntegrityReport, OrphanReport, RepairReport, TableCopy,
+};
+use rusqlite::{Connection, Result};
+use std::path::Path;
+
+/// Runs SQLite's integrity and foreign key checks. Both pragmas only read the database.
+pub fn check_integrity(conn: &Connection) -> Result<IntegrityReport> {
+    let integrity_errors = conn
+        .prepare("PRAGMA integrity_check")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|line| line != "ok")
+        .collect::<Vec<_>>();
+
+    let foreign_key_violations = conn
+        .prepare("PRAGMA foreign_key_check")?
+        .query_map([], |row| {
+            Ok(ForeignKeyViolation {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+                fkid: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(IntegrityReport {
+        ok: integrity_errors.is_empty() && foreign_key_violations.is_empty(),
+        integrity_errors,
+        foreign_key_violations,
+    })
+}
+
+/// Copies every readable row from the live database into a freshly initialized file at
+/// `target`. Rows that cannot be read, or that violate the fresh schema's constraints, are
+/// skipped rather than failing the whole copy. The new file is encrypted with `key` when there
+/// is one.
+fn dump_into(conn: &Connection, target: &Path, key: Option<&str>) -> Result<Vec<TableCopy>> {
+    {
+        let fresh = encryption::open(target, key)?;
+        fresh.execute_batch(include_str!("schema.sql"))?;
+        super::migrate(&fresh)?;
+    }
+
+    let target = target.to_string_lossy();
+    match key {
+        Some(key) => conn.execute(
+            &encryption::attach_sql("repaired", Some(key)),
+            (target.as_ref(), key),
+        )?,
+        None => conn.execute(&encryption::attach_sql("repaired", None), [target.as_ref()])?,
+    };
+
+    let result = copy_tables(conn);
+
+    conn.execute("DETACH DATABASE repaired", [])?;
+
+    result
+}
+
+fn copy_tables(conn: &Connection) -> Result<Vec<TableCopy>> {
+    let tables = conn
+        .prepare(
+            "SELECT name FROM repaired.sqlite_master
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY rowid",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut copies = Vec::new();
+
+    for table in tables {
+        let source_columns = table_columns(conn, "main", &table)?;
+        if source_columns.is_empty() {
+            continue;
+        }
+
+        let columns = table_columns(conn, "repaired", &table)?
+            .into_iter()
+            .filter(|c| source_columns.contains(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let copy = |filter: &str| {
+            format!(
+                "INSERT OR IGNORE INTO repaired.{table} ({columns}) SELECT {columns} FROM main.{table}{filter}",
+            )
+        };
+        let (rows_copied, rows_skipped) = match conn.execute(&copy(""), []) {
+            Ok(rows_copied) => (rows_copied, 0),
+            // A damaged page fails the whole statement; go row by row to save the rest.
+            Err(_) => copy_rows(conn, &table, &copy(" WHERE rowid = ?1"))?,
+        };
+
+        copies.push(TableCopy {
+            table,
+            rows_copied,
+            rows_skipped,
+        });
+    }
+
+    Ok(copies)
+}
+
+/// Runs `copy_row` for each row of `table`, returning the rows copied and the rows that could
+/// not be read. Rows whose rowid cannot even be listed are lost without being counted.
+fn copy_rows(conn: &Connection, table: &str, copy_row: &str) -> Result<(usize, usize)> {
+    let rowids = conn
+        .prepare(&format!("SELECT rowid FROM main.{}", table))?
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .map_while(Result::ok)
+        .collect::<Vec<_>>();
+
+    let mut insert = conn.prepare(copy_row)?;
+    let (mut copied, mut skipped) = (0, 0);
+    for rowid in rowids {
+        match insert.execute([rowid]) {
+            Ok(rows) => copied += rows,
+            Err(_) => skipped += 1,
+        }
+    }
+
+    Ok((copied, skipped))
+}
+
+fn table_columns(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    conn.prepare(&format!("PRAGMA {}.table_info({})", schema, table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect()
+}
+
+/// Conversations whose project still exists.
+const LIVE_CONVERSATIONS: &str =
+    "SELECT id FROM conversations WHERE project_id IN (SELECT id FROM projects)";
+
+/// Tables hanging off a project, conversation or message, with the column that points at it.
+/// Rows written before foreign keys were enforced can outlive their parent.
+const DEPENDENT_TABLES: [(&str, &str); 8] = [
+    ("requirements", "project_id"),
+    ("artifacts", "project_id"),
+    ("completeness_reports", "project_id"),
+    ("context_summaries", "conversation_id"),
+    ("drafts", "conversation_id"),
+    ("conversation_variables", "conversation_id"),
+    ("generation_debug", "message_id"),
+    ("message_embeddings", "message_id"),
+];
+
+/// `WHERE` clauses selecting the orphaned rows of `conversations`, `messages` and each
+/// dependent table. A message in an orphaned conversation counts as orphaned too.
+fn orphan_conditions() -> (String, String, Vec<(&'static str, String)>) {
+    let live_messages = format!(
+        "SELECT id FROM messages WHERE conversation_id IN ({})",
+        LIVE_CONVERSATIONS
+    );
+    let dependents = DEPENDENT_TABLES
+        .iter()
+        .map(|(table, column)| {
+            let live = match *column {
+                "message_id" => &live_messages,
+                "project_id" => "SELECT id FROM projects",
+                _ => LIVE_CONVERSATIONS,
+            };
+            (*table, format!("{} NOT IN ({})", column, live))
+        })
+        .collect();
+
+    (
+        "project_id NOT IN (SELECT id FROM projects)".to_string(),
+        format!("conversation_id NOT IN ({})", LIVE_CONVERSATIONS),
+        dependents,
+    )
+}
+
+/// Counts rows left behind by deleted projects, conversations and messages. Only reads.
+pub fn find_orphans(conn: &Connection) -> Result<OrphanReport> {
+    let (conversations, messages, dependents) = orphan_conditions();
+    let count = |table: &str, condition: &str| -> Result<usize> {
+        conn.query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE {}", table, condition),
+            [],
+            |row| row.get(0),
+        )
+    };
+
+    let mut related_rows = 0;
+    for (table, condition) in &dependents {
+        related_rows += count(table, condition)?;
+    }
+
+    Ok(OrphanReport {
+        conversations: count("conversations", &conversations)?,
+        messages: count("messages", &messages)?,
+        related_rows,
+    })
+}
+
+/// Deletes the rows `find_orphans` reports, in one transaction, and returns how many went.
+pub fn clean_orphans(conn: &mut Connection) -> Result<OrphanReport> {
+    let (conversations, messages, dependents) = orphan_conditions();
+    let tx = conn.transaction()?;
+    let delete = |table: &str, condition: &str| {
+        tx.execute(&format!("DELETE FROM {} WHERE {}", table, condition), [])
+    };
+
+    // Dependents first: their conditions are defined by the parents still in place.
+    let mut related_rows = 0;
+    for (table, condition) in &dependents {
+        related_rows += delete(table, condition)?;
+    }
+    let report = OrphanReport {
+        messages: delete("messages", &messages)?,
+        conversations: delete("conversations", &conversations)?,
+        related_rows,
+    };
+
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Truncates the WAL and rebuilds the database file without free pages. Both steps run on the
+/// live connection, which stays valid afterwards.
+pub fn compact(conn: &Connection, db_path: &Path) -> Result<CompactReport> {
+    let size_before = on_disk_size(db_path);
+
+    checkpoint(conn)?;
+    conn.execute_batch("VACUUM")?;
+    // In WAL mode VACUUM writes the rebuilt pages through the log, so truncate it again.
+    checkpoint(conn)?;
+
+    Ok(CompactReport {
+        size_before,
+        size_after: on_disk_size(db_path),
+    })
+}
+
+fn checkpoint(conn: &Connection) -> Result<()> {
+    // Returns (busy, log frames, checkpointed frames); outside WAL mode it is a no-op.
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+}
+
+/// Combined size of the database file and its WAL, in bytes.
+fn on_disk_size(db_path: &Path) -> u64 {
+    let mut wal_path = db_path.as_os_str().to_owned();
+    wal_path.push("-wal");
+
+    [db_path, Path::new(&wal_path)]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+pub fn repair(
+    conn: &mut Connection,
+    db_path: &Path,
+    key: Option<&str>,
+) -> Result<RepairReport, String> {
+    if db_path.as_os_str().is_empty() {
+        return Err("An in-memory database cannot be repaired".to_string());
+    }
+
+    let repaired_path = db_path.with_extension("repaired.db");
+    let backup_path = db_path.with_extension("corrupt.db");
+
+    if repaired_path.exists() {
+        std::fs::remove_file(&repaired_path).map_err(|e| e.to_string())?;
+    }
+
+    let swapped = dump_into(conn, &repaired_path, key)
+        .map_err(|e| e.to_string())
+        .and_then(|tables| {
+            super::swap_database_file(conn, db_path, &repaired_path, &backup_path, key, key)
+                .map(|_| tables)
+        });
+    let tables = match swapped {
+        Ok(tables) => tables,
+        Err(e) => {
+            let _ = std::fs::remove_file(&repaired_path);
+            return Err(e);
+        }
+    };
+
+    Ok(RepairReport {
+        tables,
+        backup_path: backup_path.to_string_lossy().into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use std::path::PathBuf;
+
+    /// A database file with one project in a directory of its own.
+    fn database_with_project() -> (PathBuf, Database) {
+        let dir = std::env::temp_dir().join(format!("spec-maker-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new(dir.join("spec_maker.db"), None).unwrap();
+        db.lock()
+            .execute(
+                "INSERT INTO projects (id, name, description) VALUES ('p1', 'Tracker', 'Tasks')",
+                [],
+            )
+            .unwrap();
+        (dir, db)
+    }
+
+    #[test]
+    fn finds_and_cleans_rows_of_deleted_projects() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let mut conn = db.lock();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name, description) VALUES ('p1', 'Tracker', 'Tasks');
+             INSERT INTO requirements (id, project_id, text, normalized_text)
+                 VALUES ('r1', 'p1', 'Export to CSV', 'export to csv');
+             PRAGMA foreign_keys = OFF;
+             INSERT INTO conversations (id, project_id, created_at)
+                 VALUES ('c1', 'gone', '2024-01-01T00:00:00Z');
+             INSERT INTO requirements (id, project_id, text, normalized_text)
+                 VALUES ('r2', 'gone', 'Dark mode', 'dark mode');
+             INSERT INTO artifacts (id, project_id, artifact_type, title, content)
+                 VALUES ('a1', 'gone', 'prd', 'PRD', '# PRD');
+             INSERT INTO completeness_reports (project_id, covered, missing)
+                 VALUES ('gone', '[]', '[]');
+             PRAGMA foreign_keys = ON;",
+        )
+        .unwrap();
+
+        let found = find_orphans(&conn).unwrap();
+        assert_eq!((found.conversations, found.related_rows), (1, 3));
+
+        let cleaned = clean_orphans(&mut conn).unwrap();
+        assert_eq!((cleaned.conversations, cleaned.related_rows), (1, 3));
+        let found = find_orphans(&conn).unwrap();
+        assert_eq!((found.conversations, found.related_rows), (0, 0));
+        let requirements: i64 = conn
+            .query_row("SELECT COUNT(*) FROM requirements", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(requirements, 1);
+    }
+
+    fn project_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM projects", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn repair_swaps_in_a_copy_and_keeps_the_original() {
+        let (dir, db) = database_with_project();
+        let mut conn = db.lock();
+
+        let report = repair(&mut conn, &db.path, None).unwrap();
+
+        let projects = report
+            .tables
+            .iter()
+            .find(|t| t.table == "projects")
+            .unwrap();
+        assert_eq!((projects.rows_copied, projects.rows_skipped), (1, 0));
+        assert_eq!(project_count(&conn), 1);
+        assert_eq!(conn.path().map(PathBuf::from), Some(db.path.clone()));
+        assert!(Path::new(&report.backup_path).exists());
+        assert!(!dir.join("spec_maker.repaired.db").exists());
+
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_repair_stays_on_the_original_database() {
+        let (dir, db) = database_with_project();
+        // A non-empty directory where the backup should go makes the first rename fail.
+        std::fs::create_dir_all(dir.join("spec_maker.corrupt.db").join("taken")).unwrap();
+        let mut conn = db.lock();
+
+        assert!(repair(&mut conn, &db.path, None).is_err());
+
+        assert_eq!(conn.path().map(PathBuf::from), Some(db.path.clone()));
+        assert_eq!(project_count(&conn), 1);
+        assert!(!dir.join("spec_maker.repaired.db").exists());
+
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
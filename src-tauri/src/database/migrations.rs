@@ -0,0 +1,122 @@
+//! User-supplied schema extensions, applied after the built-in schema.
+//!
+//! Each `.sql` file in the migrations directory is named `<version>_<description>.sql` and runs
+//! once, in version order, inside its own transaction. Applied files are recorded by name in
+//! `schema_migrations`, so later runs skip them.
+
+use super::models::{MigrationFailure, MigrationReport};
+use super::Database;
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+
+/// Directory under the app data dir that migrations are loaded from.
+pub const DIR_NAME: &str = "migrations";
+
+impl Database {
+    /// Applies the pending migrations in `dir`. A missing directory means there is nothing to
+    /// apply. The first file that cannot be read or executed is rolled back and reported, and
+    /// the ones after it are left pending, since they may depend on it.
+    pub fn apply_migrations(&self, dir: &Path) -> MigrationReport {
+        let mut report = MigrationReport::default();
+
+        let files = match pending_files(dir) {
+            Ok(files) => files,
+            Err(failure) => {
+                report.failed = Some(failure);
+                return report;
+            }
+        };
+
+        let mut conn = self.lock();
+        for (name, path) in files {
+            match apply(&mut conn, &name, &path) {
+                Ok(true) => report.applied.push(name),
+                Ok(false) => report.already_applied += 1,
+                Err(error) => {
+                    report.failed = Some(failure(&path, error));
+                    break;
+                }
+            }
+        }
+
+        report
+    }
+}
+
+/// Lists the `.sql` files in `dir` sorted by version, failing on one without a version prefix.
+fn pending_files(dir: &Path) -> Result<Vec<(String, PathBuf)>, MigrationFailure> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(failure(dir, e.to_string())),
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| failure(dir, e.to_string()))?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("sql") || !path.is_file() {
+            continue;
+        }
+
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| failure(&path, "File name is not valid UTF-8".to_string()))?
+            .to_string();
+        let version = version(&name).ok_or_else(|| {
+            failure(
+                &path,
+                format!(
+                    "File name must start with a version number, like 0001_{}",
+                    name
+                ),
+            )
+        })?;
+        files.push((version, name, path));
+    }
+
+    files.sort();
+    Ok(files
+        .into_iter()
+        .map(|(_, name, path)| (name, path))
+        .collect())
+}
+
+fn failure(path: &Path, error: String) -> MigrationFailure {
+    MigrationFailure {
+        file: path.display().to_string(),
+        error,
+    }
+}
+
+fn version(name: &str) -> Option<u64> {
+    let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    name[..digits].parse().ok()
+}
+
+/// Runs one migration unless it is already recorded. Returns whether it ran.
+fn apply(conn: &mut Connection, name: &str, path: &Path) -> Result<bool, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let applied: bool = tx
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM schema_migrations WHERE name = ?1)",
+            [name],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if applied {
+        return Ok(false);
+    }
+
+    let sql = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    tx.execute_batch(&sql).map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO schema_migrations (name, applied_at) VALUES (?1, ?2)",
+        (name, chrono::Utc::now().to_rfc3339()),
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
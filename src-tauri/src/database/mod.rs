@@ -1,25 +1,224 @@
+pub mod encryption;
+pub mod maintenance;
+pub mod migrations;
+pub mod response_cache;
+pub mod settings;
+
 use rusqlite::{Connection, Result};
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 
 pub struct Database {
-    pub conn: Mutex<Connection>,
+    conn: Mutex<Connection>,
+    /// Empty for in-memory databases.
+    pub path: PathBuf,
+    /// SQLCipher key the file is encrypted with, needed to reopen it after repair or rekeying.
+    key: Mutex<Option<String>>,
 }
 
 impl Database {
-    pub fn new(db_path: PathBuf) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
+    /// Opens the database at `db_path`, decrypting it with `key` when one is given.
+    pub fn new(db_path: PathBuf, key: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            key: Mutex::new(key.map(str::to_string)),
+            ..Self::from_connection(encryption::open(&db_path, key)?)?
+        })
+    }
 
+    /// Initializes the schema on an already open connection, such as
+    /// `Connection::open_in_memory()`.
+    pub fn from_connection(conn: Connection) -> Result<Self> {
         conn.execute_batch(include_str!("schema.sql"))?;
+        migrate(&conn)?;
+
+        let path = conn.path().map(PathBuf::from).unwrap_or_default();
 
         Ok(Self {
             conn: Mutex::new(conn),
+            path,
+            key: Mutex::new(None),
         })
     }
+
+    /// Locks the connection. A command that panics while holding it poisons the lock, but the
+    /// connection itself stays usable (an open transaction rolls back when dropped), so the
+    /// lock is recovered rather than failing every later command.
+    pub fn lock(&self) -> MutexGuard<'_, Connection> {
+        recover(&self.conn, "database connection")
+    }
+
+    pub fn lock_key(&self) -> MutexGuard<'_, Option<String>> {
+        recover(&self.key, "database key")
+    }
+}
+
+fn recover<'a, T>(mutex: &'a Mutex<T>, name: &str) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        eprintln!("warning: recovering {} lock poisoned by a panic", name);
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Brings databases created by older schema versions up to date.
+fn migrate(conn: &Connection) -> Result<()> {
+    add_column_if_missing(
+        conn,
+        "conversations",
+        "auto_advance",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(
+        conn,
+        "conversations",
+        "response_format",
+        "TEXT CHECK (response_format IN ('markdown', 'plain'))",
+    )?;
+    add_column_if_missing(
+        conn,
+        "projects",
+        "response_format",
+        "TEXT NOT NULL DEFAULT 'markdown' CHECK (response_format IN ('markdown', 'plain'))",
+    )?;
+    add_column_if_missing(conn, "projects", "language", "TEXT")?;
+    add_column_if_missing(conn, "conversations", "language", "TEXT")?;
+    add_column_if_missing(conn, "messages", "phase", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "conversations",
+        "source_conversation_id",
+        "TEXT REFERENCES conversations(id) ON DELETE SET NULL",
+    )?;
+    add_column_if_missing(conn, "messages", "variant_group", "TEXT")?;
+    add_column_if_missing(conn, "projects", "readonly", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(
+        conn,
+        "conversations",
+        "max_output_tokens",
+        "INTEGER CHECK (max_output_tokens > 0)",
+    )?;
+    add_column_if_missing(conn, "conversations", "updated_at", "TEXT")?;
+    conn.execute(
+        "UPDATE conversations SET updated_at = COALESCE(
+             (SELECT MAX(created_at) FROM messages WHERE conversation_id = conversations.id),
+             created_at)
+         WHERE updated_at IS NULL",
+        [],
+    )?;
+    // Created here rather than in schema.sql, which runs before older tables gain the column.
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_conversations_updated ON conversations(updated_at DESC)",
+        [],
+    )?;
+    // A prefix of idx_messages_conversation_created, which serves the same lookups.
+    conn.execute("DROP INDEX IF EXISTS idx_messages_conversation", [])?;
+    add_column_if_missing(conn, "messages", "selected", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(conn, "conversations", "ollama_version", "TEXT")?;
+    add_column_if_missing(conn, "conversations", "created_with_model", "TEXT")?;
+    add_column_if_missing(conn, "messages", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(
+        conn,
+        "messages",
+        "compressed_into",
+        "TEXT REFERENCES messages(id) ON DELETE SET NULL",
+    )?;
+    add_column_if_missing(conn, "conversations", "title", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "conversations",
+        "title_locked",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "conversations", "rolling_summary", "TEXT")?;
+    add_column_if_missing(conn, "conversations", "rolling_summary_through", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "conversations",
+        "archived",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "projects", "persona", "TEXT")?;
+
+    Ok(())
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let columns = conn
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?;
+
+    if !columns.iter().any(|c| c == column) {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the database file at `path` with `replacement`, moving the original to `backup`,
+/// and points `conn` at the new file opened with `key`.
+///
+/// The replacement is opened and read before anything is moved. The live connection is then
+/// closed, since some platforms cannot rename a file that is open, and nothing can observe the
+/// gap: the caller holds the connection lock. If a rename or the reopen fails, the files are
+/// moved back and `conn` is reopened on the original with `old_key`.
+fn swap_database_file(
+    conn: &mut Connection,
+    path: &Path,
+    replacement: &Path,
+    backup: &Path,
+    key: Option<&str>,
+    old_key: Option<&str>,
+) -> std::result::Result<(), String> {
+    encryption::open(replacement, key)
+        .and_then(|fresh| fresh.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(())))
+        .map_err(|e| format!("The new database file cannot be opened: {}", e))?;
+
+    let closed = Connection::open_in_memory().map_err(|e| e.to_string())?;
+    drop(std::mem::replace(conn, closed));
+
+    let swapped = (|| {
+        std::fs::rename(path, backup).map_err(|e| e.to_string())?;
+        if let Err(e) = std::fs::rename(replacement, path) {
+            let _ = std::fs::rename(backup, path);
+            return Err(e.to_string());
+        }
+        encryption::open(path, key).map_err(|e| {
+            let _ = std::fs::rename(path, replacement);
+            let _ = std::fs::rename(backup, path);
+            e.to_string()
+        })
+    })();
+
+    match swapped {
+        Ok(fresh) => {
+            *conn = fresh;
+            Ok(())
+        }
+        Err(e) => {
+            *conn = encryption::open(path, old_key).map_err(|reopen| {
+                format!(
+                    "{}; the original database could not be reopened: {}",
+                    e, reopen
+                )
+            })?;
+            Err(e)
+        }
+    }
 }
 
 pub mod models {
+    use crate::services::ollama::{ChatMessage, ChatOptions, ConnectionStatus};
     use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Project {
@@ -29,8 +228,16 @@ pub mod models {
         pub industry: Option<String>,
         pub target_audience: Option<String>,
         pub status: String,
+        pub response_format: String,
+        pub language: Option<String>,
+        /// Shared mode: mutating commands on the project and its conversations are rejected.
+        #[serde(default)]
+        pub readonly: bool,
         pub created_at: String,
         pub updated_at: String,
+        /// Overrides the global persona for this project's conversations.
+        #[serde(default)]
+        pub persona: Option<String>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,7 +253,43 @@ pub mod models {
         pub id: String,
         pub project_id: String,
         pub phase: String,
+        pub auto_advance: bool,
+        pub response_format: Option<String>,
+        pub language: Option<String>,
+        /// Set on conversations created by `replay_conversation` or `split_conversation_by_phase`.
+        pub source_conversation_id: Option<String>,
+        /// Estimated-token ceiling for a streamed reply, enforced client-side regardless of the
+        /// backend's own limit.
+        pub max_output_tokens: Option<u32>,
+        /// Ollama version at creation, or "unknown" if it could not be reached. `None` for
+        /// conversations created before this was recorded.
+        #[serde(default)]
+        pub ollama_version: Option<String>,
+        /// Model the conversation started on.
+        #[serde(default)]
+        pub created_with_model: Option<String>,
         pub created_at: String,
+        /// Time of the latest message, or `created_at` before the first one.
+        #[serde(default)]
+        pub updated_at: String,
+        #[serde(default)]
+        pub title: Option<String>,
+        /// The title was chosen by the user and must not be replaced automatically.
+        #[serde(default)]
+        pub title_locked: bool,
+        /// Left out of the activity feed; its messages and settings can no longer change.
+        #[serde(default)]
+        pub archived: bool,
+    }
+
+    /// A row of the cross-project activity feed.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RecentConversation {
+        #[serde(flatten)]
+        pub conversation: Conversation,
+        pub project_name: String,
+        /// Start of the latest message, if there is one.
+        pub last_message: Option<String>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,14 +299,518 @@ pub mod models {
         pub role: String,
         pub content: String,
         pub metadata: Option<String>,
+        pub phase: Option<String>,
+        /// Shared by an assistant reply and its regenerations.
+        pub variant_group: Option<String>,
+        /// Whether this is the active variant. Unselected variants are kept but left out of
+        /// the conversation history.
+        #[serde(default = "selected_by_default")]
+        pub selected: bool,
+        /// Kept verbatim when the conversation is compressed.
+        #[serde(default)]
+        pub pinned: bool,
+        pub created_at: String,
+    }
+
+    fn selected_by_default() -> bool {
+        true
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct PhaseDiff {
+        pub conversation_id: String,
+        pub from_phase: String,
+        pub to_phase: String,
+        /// Messages from the earlier phase.
+        pub context: Vec<Message>,
+        /// Messages from the later phase.
+        pub added: Vec<Message>,
+        pub summary: Option<String>,
+    }
+
+    /// The assistant reply plus an advisory when the conversation has grown past its soft
+    /// limits. Serializes as a `Message` with an optional `warning` field.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SendMessageResponse {
+        #[serde(flatten)]
+        pub message: Message,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub warning: Option<String>,
+        /// Earlier messages the reply drew on, when `cite_sources` was requested and
+        /// embeddings were available.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub sources: Option<Vec<String>>,
+        /// Every reply generated when `n` was above one, in order; `message` is the first.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub variants: Vec<Message>,
+        /// The conversation created for the message when it was sent without one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub conversation: Option<Conversation>,
+    }
+
+    /// First-run checks, each reported on its own so a setup wizard can show partial progress.
+    /// Checks that depend on a reachable server are `None` when it is not.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct OnboardingStatus {
+        pub connection: ConnectionStatus,
+        pub installed_models: Option<Vec<String>>,
+        pub configured_model: String,
+        pub configured_model_installed: Option<bool>,
+        pub completed_onboarding: bool,
+        /// The first check that has not passed yet, or `None` when setup is finished.
+        pub next_step: Option<OnboardingStep>,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum OnboardingStep {
+        /// Ollama is not installed, not running, or something else answers on its port.
+        ConnectOllama,
+        /// Ollama runs but has no models.
+        PullModel,
+        /// Models are installed, but not the configured one.
+        ChooseModel,
+    }
+
+    /// Everything `send_message` would use for a conversation's next reply.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct EffectiveConfig {
+        pub conversation_id: String,
+        /// Concrete Ollama tag, after alias resolution.
+        pub model: String,
+        pub options: ChatOptions,
+        pub phase: String,
+        pub response_format: String,
+        pub language: Option<String>,
+        pub streaming: bool,
+        /// The conversation's token ceiling, and whether it applies: it is only enforced while
+        /// streaming.
+        pub max_output_tokens: Option<u32>,
+        pub ceiling_applies: bool,
+        /// The outgoing history, starting with the composed system prompt.
+        pub messages: Vec<ChatMessage>,
+        pub estimated_prompt_tokens: u64,
+        pub warning: Option<String>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ReplayResult {
+        pub conversation: Conversation,
+        pub messages: Vec<Message>,
+    }
+
+    /// Two replays of the same conversation, aligned by user turn.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ModelComparison {
+        pub source_conversation_id: String,
+        pub model_a: String,
+        pub model_b: String,
+        /// Replay conversations holding each model's answers.
+        pub conversation_a: Conversation,
+        pub conversation_b: Conversation,
+        pub turns: Vec<ComparedTurn>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ComparedTurn {
+        pub prompt: String,
+        pub phase: Option<String>,
+        pub response_a: Option<Message>,
+        pub response_b: Option<Message>,
+    }
+
+    /// Generation state stored as JSON in an assistant message's `metadata` column.
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    pub struct AssistantMetadata {
+        #[serde(default)]
+        pub complete: bool,
+        #[serde(default)]
+        pub cancelled: bool,
+        /// The stream was cut off at the conversation's output token ceiling.
+        #[serde(default)]
+        pub truncated: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub format: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub language: Option<String>,
+        /// Model and seed the reply was generated with, when they were set explicitly.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub model: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub seed: Option<i64>,
+        /// Ollama's reason for ending the reply, such as `stop` or `length`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub done_reason: Option<String>,
+        /// The reply was reused from the response cache instead of generated.
+        #[serde(default)]
+        pub from_cache: bool,
+        /// Ids of the earlier messages most similar to the reply, best first.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub sources: Vec<String>,
+        /// The model that failed to load when a fallback answered instead; `model` is then the
+        /// fallback.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub fallback_from: Option<String>,
+    }
+
+    impl AssistantMetadata {
+        pub fn to_json(&self) -> String {
+            serde_json::to_string(self).unwrap_or_default()
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RollingSummary {
+        pub conversation_id: String,
+        /// `None` until the first update.
+        pub content: Option<String>,
+        /// Creation time of the last message the summary covers.
+        pub through: Option<String>,
+    }
+
+    /// A streamed reply that never finished, left behind by a crash or a failed stream.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct InterruptedGeneration {
+        pub message_id: String,
+        pub conversation_id: String,
+        pub project_id: String,
+        pub phase: Option<String>,
+        /// Whatever was persisted before the stream stopped.
+        pub partial_content: String,
         pub created_at: String,
     }
 
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct MessageChunkEvent {
+        pub conversation_id: String,
+        pub message_id: String,
+        pub delta: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct EmbeddingProgressEvent {
+        pub project_id: String,
+        pub embedded: usize,
+        pub total: usize,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ReindexSummary {
+        pub project_id: String,
+        pub model: String,
+        pub embedded: usize,
+        /// Messages that already had an embedding from this model.
+        pub skipped: usize,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct PhaseCompleteEvent {
+        pub conversation_id: String,
+        pub phase: String,
+        pub next_phase: Option<String>,
+        pub advanced: bool,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ForeignKeyViolation {
+        pub table: String,
+        pub rowid: Option<i64>,
+        pub parent: String,
+        pub fkid: i64,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct IntegrityReport {
+        pub ok: bool,
+        pub integrity_errors: Vec<String>,
+        pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct TableCopy {
+        pub table: String,
+        pub rows_copied: usize,
+        /// Rows that could not be read from the damaged database.
+        #[serde(default)]
+        pub rows_skipped: usize,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RepairReport {
+        pub tables: Vec<TableCopy>,
+        pub backup_path: String,
+    }
+
+    /// Outcome of applying the user migrations directory at startup.
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    pub struct MigrationReport {
+        /// Files applied by this run, in order.
+        pub applied: Vec<String>,
+        pub already_applied: usize,
+        pub failed: Option<MigrationFailure>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct MigrationFailure {
+        pub file: String,
+        pub error: String,
+    }
+
+    /// Rows whose parent no longer exists, found by `find_orphans` or removed by `clean_orphans`.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct OrphanReport {
+        pub conversations: usize,
+        pub messages: usize,
+        /// Requirements, artifacts and completeness reports of missing projects, plus drafts,
+        /// variables, summaries, embeddings and debug captures of orphaned or missing
+        /// conversations and messages.
+        pub related_rows: usize,
+    }
+
+    /// Database size in bytes, including the WAL, around a `compact_database` run.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct CompactReport {
+        pub size_before: u64,
+        pub size_after: u64,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Requirement {
+        pub id: String,
+        pub project_id: String,
+        pub text: String,
+        pub priority: String,
+        pub status: String,
+        pub created_at: String,
+        pub updated_at: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct CreateRequirementInput {
+        pub project_id: String,
+        pub text: String,
+        pub priority: Option<String>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct UpdateRequirementInput {
+        pub text: Option<String>,
+        pub priority: Option<String>,
+        pub status: Option<String>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ConversationExport {
+        pub conversation: Conversation,
+        pub messages: Vec<Message>,
+        /// Messages hidden by `compress_conversation`, mapped to the summary that replaced them.
+        #[serde(default)]
+        pub compressed_into: HashMap<String, String>,
+        #[serde(default)]
+        pub rolling_summary: Option<String>,
+        #[serde(default)]
+        pub rolling_summary_through: Option<String>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ProjectExport {
+        pub project: Project,
+        pub requirements: Vec<Requirement>,
+        pub conversations: Vec<ConversationExport>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ArchiveSummary {
+        pub path: String,
+        pub projects: usize,
+        pub conversations: usize,
+        pub messages: usize,
+    }
+
+    /// A project archive written by `delete_project`, restorable with `restore_backup`.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct BackupInfo {
+        pub file_name: String,
+        pub path: String,
+        pub size_bytes: u64,
+        pub created_at: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ImportSummary {
+        /// Ids assigned to the newly created projects.
+        pub imported: Vec<String>,
+        /// Projects whose content already exists in the database.
+        pub skipped: usize,
+        /// Timestamps rewritten to RFC 3339 UTC or clamped to the import time.
+        pub timestamps_fixed: usize,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct TimestampReport {
+        pub checked: usize,
+        pub fixed: usize,
+        /// Values that could not be parsed; they are left as they are.
+        pub invalid: Vec<InvalidTimestamp>,
+        /// Values of read-only projects, which are not checked.
+        pub skipped_readonly: usize,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct InvalidTimestamp {
+        pub table: String,
+        pub id: String,
+        pub column: String,
+        pub value: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct CompletenessReport {
+        pub project_id: String,
+        pub covered: Vec<String>,
+        pub missing: Vec<String>,
+        pub created_at: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct GenerationDebug {
+        pub message_id: String,
+        pub request: String,
+        pub response: String,
+        pub created_at: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StopAllSummary {
+        pub cancelled: usize,
+        pub model_unloaded: bool,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ConversationVariable {
+        pub conversation_id: String,
+        pub name: String,
+        pub value: String,
+        pub updated_at: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Draft {
+        pub conversation_id: String,
+        pub content: String,
+        pub updated_at: String,
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct CreateMessageInput {
+        /// Empty to start a new conversation in `project_id`.
+        #[serde(default)]
         pub conversation_id: String,
         pub role: String,
         pub content: String,
         pub metadata: Option<String>,
+        /// Model or alias to answer with instead of the configured default.
+        #[serde(default)]
+        pub model: Option<String>,
+        /// Attribute the reply to earlier messages by embedding similarity. Adds an embedding
+        /// request after generation.
+        #[serde(default)]
+        pub cite_sources: bool,
+        /// Number of alternative replies to generate, stored as one variant group.
+        #[serde(default)]
+        pub n: Option<u32>,
+        /// Project to create the conversation in when `conversation_id` is empty.
+        #[serde(default)]
+        pub project_id: Option<String>,
+        /// Accept blank content, for a turn that only asks the model to continue.
+        #[serde(default)]
+        pub allow_empty: bool,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ModelAlias {
+        pub alias: String,
+        pub model: String,
+        pub updated_at: String,
+    }
+
+    /// A batch to estimate with `estimate_batch`: `prompts`, or the next turn of
+    /// `conversation_id`, each generated `n` times.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct EstimateBatchInput {
+        #[serde(default)]
+        pub prompts: Vec<String>,
+        #[serde(default)]
+        pub conversation_id: Option<String>,
+        #[serde(default)]
+        pub n: Option<u32>,
+        /// Model or alias whose saved options apply.
+        #[serde(default)]
+        pub model: Option<String>,
+    }
+
+    /// Options saved for one model. `None` falls back to the global setting.
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct ModelOptions {
+        pub temperature: Option<f32>,
+        pub top_p: Option<f32>,
+        pub num_ctx: Option<u32>,
+        pub max_tokens: Option<u32>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ModelSettings {
+        pub model: String,
+        #[serde(flatten)]
+        pub options: ModelOptions,
+        pub updated_at: String,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM projects", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn a_panic_while_locked_does_not_break_later_commands() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+
+        let panicked = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let mut conn = db.lock();
+                    let tx = conn.transaction().unwrap();
+                    tx.execute(
+                        "INSERT INTO projects (id, name, description) VALUES ('p1', 'Lost', '')",
+                        [],
+                    )
+                    .unwrap();
+                    let _key = db.lock_key();
+                    panic!("command failed mid-transaction");
+                })
+                .join()
+                .is_err()
+        });
+        assert!(panicked);
+        assert!(db.conn.is_poisoned() && db.key.is_poisoned());
+
+        let conn = db.lock();
+        // The interrupted transaction rolled back when its guard dropped.
+        assert_eq!(project_count(&conn), 0);
+        conn.execute(
+            "INSERT INTO projects (id, name, description) VALUES ('p2', 'Kept', '')",
+            [],
+        )
+        .unwrap();
+        assert_eq!(project_count(&conn), 1);
+        assert!(!db.conn.is_poisoned());
+        assert_eq!(*db.lock_key(), None);
+        assert!(!db.key.is_poisoned());
     }
 }
@@ -0,0 +1,188 @@
+//! Replies cached by a hash of the exact request, reused only when generation is deterministic.
+
+use crate::services::ollama::{ChatMessage, ChatOptions};
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+/// Entries kept before the least recently used are evicted.
+pub const MAX_ENTRIES: usize = 500;
+
+/// A reply can only be reused if the same request always produces it: no sampling randomness
+/// and a fixed seed.
+pub fn is_deterministic(options: &ChatOptions) -> bool {
+    options.temperature == 0.0 && options.seed.is_some()
+}
+
+/// Identifies a request in the cache.
+pub struct RequestKey {
+    pub hash: String,
+    pub model: String,
+}
+
+impl RequestKey {
+    pub fn new(model: &str, options: &ChatOptions, messages: &[ChatMessage]) -> Self {
+        let request = json!({
+            "model": model,
+            "options": options,
+            "messages": messages,
+        });
+
+        Self {
+            hash: format!("{:x}", Sha256::digest(request.to_string())),
+            model: model.to_string(),
+        }
+    }
+}
+
+/// Returns the cached reply for `key`, marking it as recently used.
+pub fn get(conn: &Connection, key: &RequestKey) -> Result<Option<String>, String> {
+    let response: Option<String> = conn
+        .query_row(
+            "SELECT response FROM response_cache WHERE key = ?1",
+            [&key.hash],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    if response.is_some() {
+        conn.execute(
+            "UPDATE response_cache SET last_used_at = ?1, hits = hits + 1 WHERE key = ?2",
+            (chrono::Utc::now().to_rfc3339(), &key.hash),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    Ok(response)
+}
+
+pub fn put(conn: &Connection, key: &RequestKey, response: &str) -> Result<(), String> {
+    let now = chrono::Utc::now().to_rfc3339();
+
+    conn.execute(
+        "INSERT OR REPLACE INTO response_cache (key, model, response, created_at, last_used_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)",
+        (&key.hash, &key.model, response, &now),
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "DELETE FROM response_cache WHERE key IN (
+             SELECT key FROM response_cache ORDER BY last_used_at DESC LIMIT -1 OFFSET ?1)",
+        [MAX_ENTRIES],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn clear(conn: &Connection) -> Result<usize, String> {
+    conn.execute("DELETE FROM response_cache", [])
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    fn options(temperature: f32, seed: Option<i64>) -> ChatOptions {
+        ChatOptions {
+            temperature,
+            num_predict: None,
+            seed,
+            num_ctx: None,
+            top_p: None,
+            stop: None,
+        }
+    }
+
+    fn messages(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+            images: Vec::new(),
+        }]
+    }
+
+    fn key(name: &str) -> RequestKey {
+        RequestKey {
+            hash: name.to_string(),
+            model: "llama3.1:8b".to_string(),
+        }
+    }
+
+    #[test]
+    fn only_seeded_zero_temperature_requests_are_cached() {
+        assert!(is_deterministic(&options(0.0, Some(1))));
+        assert!(!is_deterministic(&options(0.0, None)));
+        assert!(!is_deterministic(&options(0.2, Some(1))));
+    }
+
+    #[test]
+    fn keys_cover_the_model_options_and_history() {
+        let base = RequestKey::new("llama3.1:8b", &options(0.0, Some(1)), &messages("Hi"));
+        let hash = |model: &str, options: ChatOptions, content: &str| {
+            RequestKey::new(model, &options, &messages(content)).hash
+        };
+
+        assert_eq!(hash("llama3.1:8b", options(0.0, Some(1)), "Hi"), base.hash);
+        assert_ne!(hash("qwen2.5:7b", options(0.0, Some(1)), "Hi"), base.hash);
+        assert_ne!(hash("llama3.1:8b", options(0.0, Some(2)), "Hi"), base.hash);
+        assert_ne!(hash("llama3.1:8b", options(0.0, Some(1)), "Hi!"), base.hash);
+    }
+
+    #[test]
+    fn hits_are_returned_and_counted() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.lock();
+
+        assert_eq!(get(&conn, &key("a")).unwrap(), None);
+        put(&conn, &key("a"), "Cached reply").unwrap();
+        assert_eq!(
+            get(&conn, &key("a")).unwrap().as_deref(),
+            Some("Cached reply")
+        );
+        get(&conn, &key("a")).unwrap();
+
+        let hits: i64 = conn
+            .query_row(
+                "SELECT hits FROM response_cache WHERE key = 'a'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hits, 2);
+        assert_eq!(clear(&conn).unwrap(), 1);
+        assert_eq!(get(&conn, &key("a")).unwrap(), None);
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted() {
+        let db = Database::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let conn = db.lock();
+        // A full cache whose entries were last used in order, oldest first.
+        conn.execute(
+            "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i + 1 < ?1)
+             INSERT INTO response_cache (key, model, response, created_at, last_used_at)
+             SELECT 'k' || i, 'llama3.1:8b', 'reply', '2024-01-01T00:00:00Z',
+                    printf('2024-01-01T00:%02d:%02dZ', i / 60, i % 60)
+             FROM n",
+            [MAX_ENTRIES],
+        )
+        .unwrap();
+
+        // Using the oldest entry saves it; the next oldest goes instead.
+        get(&conn, &key("k0")).unwrap().unwrap();
+        put(&conn, &key("new"), "reply").unwrap();
+
+        let count: usize = conn
+            .query_row("SELECT COUNT(*) FROM response_cache", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, MAX_ENTRIES);
+        assert!(get(&conn, &key("k0")).unwrap().is_some());
+        assert!(get(&conn, &key("k1")).unwrap().is_none());
+        assert!(get(&conn, &key("new")).unwrap().is_some());
+    }
+}
@@ -9,8 +9,12 @@ CREATE TABLE IF NOT EXISTS projects (
     target_audience TEXT,
     status TEXT NOT NULL DEFAULT 'ideation'
         CHECK (status IN ('ideation', 'consultation', 'generating', 'review', 'complete')),
+    response_format TEXT NOT NULL DEFAULT 'markdown' CHECK (response_format IN ('markdown', 'plain')),
+    language TEXT,
+    readonly INTEGER NOT NULL DEFAULT 0,
     created_at TEXT NOT NULL DEFAULT (datetime('now')),
-    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    persona TEXT
 );
 
 -- Conversations: Chat sessions within a project
@@ -19,7 +23,25 @@ CREATE TABLE IF NOT EXISTS conversations (
     project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
     phase TEXT NOT NULL DEFAULT 'initial_analysis'
         CHECK (phase IN ('initial_analysis', 'consultation', 'context_building', 'generation', 'refinement')),
-    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+    auto_advance INTEGER NOT NULL DEFAULT 0,
+    response_format TEXT CHECK (response_format IN ('markdown', 'plain')),
+    language TEXT,
+    source_conversation_id TEXT REFERENCES conversations(id) ON DELETE SET NULL,
+    max_output_tokens INTEGER CHECK (max_output_tokens > 0),
+    -- Ollama version ('unknown' if it was unreachable) and default model at creation.
+    ollama_version TEXT,
+    created_with_model TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    -- Time of the latest message, maintained by trg_messages_touch_conversation.
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    title TEXT,
+    -- Set once the user picks a title; automatic titling must leave it alone.
+    title_locked INTEGER NOT NULL DEFAULT 0,
+    -- Running summary sent with every turn, and the created_at of the last message folded in.
+    rolling_summary TEXT,
+    rolling_summary_through TEXT,
+    -- Hidden from the activity feed and closed to changes, as after a split.
+    archived INTEGER NOT NULL DEFAULT 0
 );
 
 -- Messages: Individual chat messages
@@ -29,6 +51,14 @@ CREATE TABLE IF NOT EXISTS messages (
     role TEXT NOT NULL CHECK (role IN ('user', 'assistant', 'system')),
     content TEXT NOT NULL,
     metadata TEXT,
+    phase TEXT,
+    -- Regenerated replies share a group; only the selected variant is part of the history.
+    variant_group TEXT,
+    selected INTEGER NOT NULL DEFAULT 1,
+    -- Pinned messages are never folded into a summary by compress_conversation.
+    pinned INTEGER NOT NULL DEFAULT 0,
+    -- Set on originals kept after compression; they stay unselected until restored.
+    compressed_into TEXT REFERENCES messages(id) ON DELETE SET NULL,
     created_at TEXT NOT NULL DEFAULT (datetime('now'))
 );
 
@@ -58,11 +88,119 @@ CREATE TABLE IF NOT EXISTS context_summaries (
     created_at TEXT NOT NULL DEFAULT (datetime('now'))
 );
 
+-- Drafts: In-progress message text, one per conversation
+CREATE TABLE IF NOT EXISTS drafts (
+    conversation_id TEXT PRIMARY KEY REFERENCES conversations(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Conversation Variables: {{name}} placeholders substituted into outgoing user messages
+CREATE TABLE IF NOT EXISTS conversation_variables (
+    conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (conversation_id, name)
+);
+
+-- Requirements: Individually tracked requirements extracted from project discussion
+CREATE TABLE IF NOT EXISTS requirements (
+    id TEXT PRIMARY KEY,
+    project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    normalized_text TEXT NOT NULL,
+    priority TEXT NOT NULL DEFAULT 'medium' CHECK (priority IN ('high', 'medium', 'low')),
+    status TEXT NOT NULL DEFAULT 'proposed'
+        CHECK (status IN ('proposed', 'accepted', 'done', 'rejected')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (project_id, normalized_text)
+);
+
+-- Completeness Reports: Latest spec completeness review per project
+CREATE TABLE IF NOT EXISTS completeness_reports (
+    project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+    covered TEXT NOT NULL,
+    missing TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Generation Debug: Raw Ollama request/response captured while debug mode is on
+CREATE TABLE IF NOT EXISTS generation_debug (
+    id TEXT PRIMARY KEY,
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    request TEXT NOT NULL,
+    response TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Model Aliases: Logical model names mapped to locally installed Ollama tags
+CREATE TABLE IF NOT EXISTS model_aliases (
+    alias TEXT PRIMARY KEY,
+    model TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Model Settings: Sampling defaults saved for one model, used whenever it is selected
+CREATE TABLE IF NOT EXISTS model_settings (
+    model TEXT PRIMARY KEY,
+    temperature REAL,
+    top_p REAL,
+    num_ctx INTEGER CHECK (num_ctx > 0),
+    max_tokens INTEGER CHECK (max_tokens > 0),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Message Embeddings: One vector per message and embedding model
+CREATE TABLE IF NOT EXISTS message_embeddings (
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    model TEXT NOT NULL,
+    dimensions INTEGER NOT NULL,
+    -- Little-endian f32 values.
+    vector BLOB NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (message_id, model)
+);
+
+-- Response Cache: Replies to deterministic requests, keyed by a hash of the request
+CREATE TABLE IF NOT EXISTS response_cache (
+    key TEXT PRIMARY KEY,
+    model TEXT NOT NULL,
+    response TEXT NOT NULL,
+    hits INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    last_used_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Settings: Persistent preferences, one JSON value per key
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Schema migrations: User-supplied migration files already applied, by file name
+CREATE TABLE IF NOT EXISTS schema_migrations (
+    name TEXT PRIMARY KEY,
+    applied_at TEXT NOT NULL
+);
+
+CREATE TRIGGER IF NOT EXISTS trg_messages_touch_conversation
+AFTER INSERT ON messages
+BEGIN
+    UPDATE conversations SET updated_at = MAX(updated_at, NEW.created_at)
+    WHERE id = NEW.conversation_id;
+END;
+
 -- Indexes for performance
 CREATE INDEX IF NOT EXISTS idx_projects_status ON projects(status);
 CREATE INDEX IF NOT EXISTS idx_projects_updated ON projects(updated_at DESC);
 CREATE INDEX IF NOT EXISTS idx_conversations_project ON conversations(project_id);
-CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(conversation_id);
 CREATE INDEX IF NOT EXISTS idx_messages_created ON messages(created_at);
+CREATE INDEX IF NOT EXISTS idx_messages_conversation_created ON messages(conversation_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_requirements_project ON requirements(project_id);
 CREATE INDEX IF NOT EXISTS idx_artifacts_project ON artifacts(project_id);
 CREATE INDEX IF NOT EXISTS idx_artifacts_type ON artifacts(artifact_type);
+CREATE INDEX IF NOT EXISTS idx_generation_debug_message ON generation_debug(message_id);
+CREATE INDEX IF NOT EXISTS idx_response_cache_used ON response_cache(last_used_at);
@@ -0,0 +1,109 @@
+//! Persistent key-value settings. Values are stored as JSON, so each caller reads them back as
+//! whatever type it wrote.
+
+use super::Database;
+use rusqlite::{Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const MODEL: &str = "model";
+pub const EMBEDDING_MODEL: &str = "embedding_model";
+pub const TEMPERATURE: &str = "temperature";
+pub const SEED: &str = "seed";
+pub const MAX_TOKENS: &str = "max_tokens";
+pub const STOP_SEQUENCES: &str = "stop_sequences";
+pub const STREAMING: &str = "streaming";
+pub const HEALTH_CHECK_INTERVAL_SECS: &str = "health_check_interval_secs";
+pub const LATENCY_SAMPLES: &str = "latency_samples";
+/// User turns between rolling summary updates; 0 turns them off.
+pub const ROLLING_SUMMARY_INTERVAL: &str = "rolling_summary_interval";
+/// Cadence of `message-chunk` events: the most milliseconds and deltas held back per event.
+pub const STREAM_EMIT_INTERVAL_MS: &str = "stream_emit_interval_ms";
+pub const STREAM_EMIT_MAX_CHUNKS: &str = "stream_emit_max_chunks";
+/// Whether conversations started from `send_message` get a generated title.
+pub const AUTO_TITLE: &str = "auto_title";
+/// The assistant's voice for projects without a persona of their own.
+pub const PERSONA: &str = "persona";
+/// Models tried in order when the selected one fails to load.
+pub const FALLBACK_MODELS: &str = "fallback_models";
+pub const BACKUP_BEFORE_DELETE: &str = "backup_before_delete";
+pub const CONVERSATION_LIMITS: &str = "conversation_limits";
+pub const COMPLETED_ONBOARDING: &str = "completed_onboarding";
+
+pub fn get<T: DeserializeOwned>(conn: &Connection, key: &str) -> Result<Option<T>, String> {
+    let value: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    value
+        .map(|value| {
+            serde_json::from_str(&value)
+                .map_err(|e| format!("Invalid value for setting {}: {}", key, e))
+        })
+        .transpose()
+}
+
+pub fn set<T: Serialize + ?Sized>(conn: &Connection, key: &str, value: &T) -> Result<(), String> {
+    let value = serde_json::to_string(value).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        (key, &value, chrono::Utc::now().to_rfc3339()),
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn all(conn: &Connection) -> Result<BTreeMap<String, Value>, String> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM settings")
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|(key, value)| {
+            let value = serde_json::from_str(&value)
+                .map_err(|e| format!("Invalid value for setting {}: {}", key, e))?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// Reads `value` as the type a setting is stored as, so a bad value is rejected when it is set
+/// rather than when it is loaded.
+pub fn parse<T: DeserializeOwned>(key: &str, value: &Value) -> Result<T, String> {
+    serde_json::from_value(value.clone())
+        .map_err(|e| format!("Invalid value for setting {}: {}", key, e))
+}
+
+impl Database {
+    pub fn get_setting<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, String> {
+        let conn = self.lock();
+        get(&conn, key)
+    }
+
+    pub fn set_setting<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> Result<(), String> {
+        let conn = self.lock();
+        set(&conn, key, value)
+    }
+
+    /// The stored value, or `default` when the setting is unset or unreadable. Used at startup,
+    /// where a bad value should not keep the app from opening.
+    pub fn setting_or<T: DeserializeOwned>(&self, key: &str, default: T) -> T {
+        self.get_setting(key).ok().flatten().unwrap_or(default)
+    }
+}
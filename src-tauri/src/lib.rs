@@ -2,10 +2,44 @@ mod commands;
 mod database;
 mod services;
 
-use database::Database;
+use database::{encryption, migrations, settings, Database};
+use services::generation::GenerationRegistry;
+use services::health::{self, HealthMonitor};
+use services::limits::LimitSettings;
 use services::ollama::{OllamaConfig, OllamaService};
+use services::recommend;
 use std::path::PathBuf;
-use tauri::Manager;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, RunEvent};
+
+/// How long a first run waits on Ollama to recommend a model before keeping the built-in one.
+const RECOMMENDATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// On a first run, switches to the best installed model and saves it as the model setting, so
+/// later starts use it without asking Ollama again. Keeps the built-in model when Ollama is
+/// unreachable or has no chat model installed.
+async fn adopt_recommended_model(app: AppHandle) {
+    let ollama = app.state::<OllamaService>();
+    let Ok(Ok(recommendation)) =
+        tokio::time::timeout(RECOMMENDATION_TIMEOUT, recommend::recommend(&ollama)).await
+    else {
+        return;
+    };
+    let Some(model) = recommendation.recommended else {
+        return;
+    };
+
+    let db = app.state::<Database>();
+    // A model chosen while the probe ran wins over the recommendation.
+    if !matches!(db.get_setting::<String>(settings::MODEL), Ok(None)) {
+        return;
+    }
+    if let Err(error) = db.set_setting(settings::MODEL, &model) {
+        eprintln!("warning: could not save the recommended model: {}", error);
+        return;
+    }
+    ollama.set_default_model(model);
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -16,11 +50,64 @@ pub fn run() {
             std::fs::create_dir_all(&app_dir).expect("Failed to create app data directory");
             let db_path: PathBuf = app_dir.join("specmaker.db");
 
-            let db = Database::new(db_path).expect("Failed to initialize database");
+            let db_key = std::env::var(encryption::KEY_ENV)
+                .ok()
+                .filter(|key| !key.is_empty());
+            let db = Database::new(db_path, db_key.as_deref())
+                .expect("Failed to initialize database");
+
+            // A broken user migration is reported rather than preventing startup.
+            let migration_report = db.apply_migrations(&app_dir.join(migrations::DIR_NAME));
+            if let Some(failure) = &migration_report.failed {
+                eprintln!(
+                    "warning: migration {} failed and was rolled back: {}",
+                    failure.file, failure.error
+                );
+            }
+
+            let defaults = OllamaConfig::default();
+            let saved_model = db.get_setting::<String>(settings::MODEL).ok().flatten();
+            let first_run = saved_model.is_none();
+            let ollama_config = OllamaConfig {
+                base_url: defaults.base_url,
+                model: saved_model.unwrap_or(defaults.model),
+                embedding_model: db
+                    .setting_or(settings::EMBEDDING_MODEL, defaults.embedding_model),
+                temperature: db.setting_or(settings::TEMPERATURE, defaults.temperature),
+                seed: db.setting_or(settings::SEED, defaults.seed),
+                max_tokens: db.setting_or(settings::MAX_TOKENS, defaults.max_tokens),
+                stop: db.setting_or(settings::STOP_SEQUENCES, defaults.stop),
+                streaming: db.setting_or(settings::STREAMING, defaults.streaming),
+                health_check_interval_secs: db.setting_or(
+                    settings::HEALTH_CHECK_INTERVAL_SECS,
+                    defaults.health_check_interval_secs,
+                ),
+                latency_samples: db
+                    .setting_or(settings::LATENCY_SAMPLES, defaults.latency_samples),
+            };
+            let limits = db.setting_or(settings::CONVERSATION_LIMITS, Default::default());
             app.manage(db);
+            app.manage(migration_report);
+
+            let health_check_interval =
+                Duration::from_secs(ollama_config.health_check_interval_secs);
 
-            let ollama_service = OllamaService::new(OllamaConfig::default());
+            let ollama_service = OllamaService::new(ollama_config);
             app.manage(ollama_service);
+            app.manage(GenerationRegistry::default());
+            app.manage(LimitSettings::new(limits));
+
+            let health_monitor = HealthMonitor::default();
+            tauri::async_runtime::spawn(health::poll(
+                app.handle().clone(),
+                health_check_interval,
+                health_monitor.shutdown_token(),
+            ));
+            app.manage(health_monitor);
+
+            if first_run {
+                tauri::async_runtime::spawn(adopt_recommended_model(app.handle().clone()));
+            }
 
             Ok(())
         })
@@ -28,12 +115,108 @@ pub fn run() {
             commands::create_project,
             commands::get_projects,
             commands::get_project,
+            commands::create_from_project,
+            commands::set_project_readonly,
             commands::delete_project,
+            commands::get_recent_conversations,
             commands::create_conversation,
+            commands::get_conversation,
+            commands::rename_conversation,
+            commands::move_conversation,
+            commands::set_conversation_archived,
+            commands::split_conversation_by_phase,
+            commands::set_auto_advance,
+            commands::advance_phase,
+            commands::set_project_response_format,
+            commands::set_conversation_response_format,
+            commands::set_project_language,
+            commands::get_persona,
+            commands::set_persona,
+            commands::set_conversation_language,
+            commands::set_conversation_token_ceiling,
             commands::get_conversation_messages,
             commands::send_message,
+            commands::stream_message,
+            commands::resolve_effective_config,
+            commands::get_resolved_messages,
+            commands::add_image_message,
+            commands::regenerate_last_response,
+            commands::regenerate_message,
+            commands::suggest_followups,
+            commands::get_interrupted_generations,
+            commands::resume_or_discard,
+            commands::select_variant,
+            commands::set_message_pinned,
+            commands::compress_conversation,
+            commands::restore_compressed,
+            commands::get_rolling_summary,
+            commands::regenerate_rolling_summary,
+            commands::cancel_generation,
+            commands::stop_all,
+            commands::replay_conversation,
+            commands::compare_models,
+            commands::diff_phases,
+            commands::preview_template,
+            commands::set_conversation_variable,
+            commands::list_conversation_variables,
+            commands::save_draft,
+            commands::get_draft,
+            commands::clear_draft,
+            commands::check_spec_completeness,
+            commands::get_completeness_report,
+            commands::extract_requirements,
+            commands::list_requirements,
+            commands::create_requirement,
+            commands::update_requirement,
+            commands::delete_requirement,
+            commands::export_project_markdown,
+            commands::export_project_csv,
+            commands::export_conversation_plain,
+            commands::export_decisions,
+            commands::redact_project,
+            commands::reindex_embeddings,
+            commands::export_all,
+            commands::import_all,
+            commands::list_backups,
+            commands::restore_backup,
+            commands::normalize_timestamps,
+            commands::check_database_integrity,
+            commands::get_migration_report,
+            commands::repair_database,
+            commands::set_encryption_key,
+            commands::compact_database,
+            commands::find_orphans,
+            commands::clean_orphans,
+            commands::clear_response_cache,
+            commands::get_conversation_limits,
+            commands::set_conversation_limits,
+            commands::get_stop_sequences,
+            commands::set_stop_sequences,
+            commands::get_all_settings,
+            commands::set_setting,
+            commands::get_latency_stats,
+            commands::set_debug_mode,
+            commands::get_generation_debug,
+            commands::list_model_aliases,
+            commands::set_model_alias,
+            commands::delete_model_alias,
+            commands::get_model_settings,
+            commands::set_model_settings,
+            commands::set_streaming_enabled,
+            commands::get_capabilities,
+            commands::get_onboarding_status,
+            commands::set_onboarding_completed,
             commands::check_ollama_connection,
+            commands::get_model_info,
+            commands::recommend_model,
+            commands::benchmark_models,
+            commands::estimate_batch,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            if let RunEvent::Exit = event {
+                app_handle.state::<HealthMonitor>().shutdown();
+            }
+        });
 }
@@ -0,0 +1,165 @@
+//! Whole-database archive format used by `export_all` and `import_all`.
+//!
+//! An archive is a single JSON object: `{"version": 1, "exported_at": ..., "projects": [...]}`.
+//! Each project entry is a `ProjectExport` plus a content hash, so re-importing an archive into
+//! a database that already holds the same projects skips them instead of duplicating them.
+
+use crate::database::models::ProjectExport;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub projects: Vec<ArchivedProject>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedProject {
+    pub hash: String,
+    #[serde(flatten)]
+    pub export: ProjectExport,
+}
+
+/// Writes an archive one project at a time, so the whole dataset never has to be held as a
+/// single string.
+pub struct ArchiveWriter<W: Write> {
+    writer: W,
+    written: usize,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, String> {
+        write!(
+            writer,
+            r#"{{"version":{},"exported_at":{},"projects":["#,
+            ARCHIVE_VERSION,
+            json!(chrono::Utc::now().to_rfc3339())
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(Self { writer, written: 0 })
+    }
+
+    pub fn write_project(&mut self, export: ProjectExport) -> Result<(), String> {
+        if self.written > 0 {
+            self.writer.write_all(b",").map_err(|e| e.to_string())?;
+        }
+
+        let entry = ArchivedProject {
+            hash: content_hash(&export),
+            export,
+        };
+        serde_json::to_writer(&mut self.writer, &entry).map_err(|e| e.to_string())?;
+        self.written += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W, String> {
+        self.writer.write_all(b"]}").map_err(|e| e.to_string())?;
+        self.writer.flush().map_err(|e| e.to_string())?;
+        Ok(self.writer)
+    }
+}
+
+pub fn read(reader: impl Read) -> Result<Archive, String> {
+    let archive: Archive =
+        serde_json::from_reader(reader).map_err(|e| format!("Invalid archive: {}", e))?;
+
+    if archive.version != ARCHIVE_VERSION {
+        return Err(format!(
+            "Unsupported archive version {} (expected {})",
+            archive.version, ARCHIVE_VERSION
+        ));
+    }
+
+    Ok(archive)
+}
+
+/// Backup file name for a project deleted at `now`: a slug of its name, a UTC timestamp and the
+/// project id, such as `checkout-redesign-20240501T120000Z-<id>.json`. The id keeps two
+/// same-named projects deleted within a second apart.
+pub fn backup_file_name(
+    project_name: &str,
+    project_id: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let slug = project_name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug: String = slug.chars().take(BACKUP_SLUG_MAX_CHARS).collect();
+    let slug = slug.trim_end_matches('-');
+
+    format!(
+        "{}-{}-{}.json",
+        if slug.is_empty() { "project" } else { slug },
+        now.format("%Y%m%dT%H%M%SZ"),
+        project_id
+    )
+}
+
+const BACKUP_SLUG_MAX_CHARS: usize = 48;
+
+/// Hashes what the user wrote and discussed, ignoring ids and timestamps, which change when a
+/// project is imported.
+pub fn content_hash(export: &ProjectExport) -> String {
+    let project = &export.project;
+    let content = json!({
+        "project": [
+            project.name,
+            project.description,
+            project.industry,
+            project.target_audience,
+        ],
+        "requirements": export
+            .requirements
+            .iter()
+            .map(|requirement| &requirement.text)
+            .collect::<Vec<_>>(),
+        "conversations": export
+            .conversations
+            .iter()
+            .map(|conversation| {
+                conversation
+                    .messages
+                    .iter()
+                    .map(|message| [&message.role, &message.content])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>(),
+    });
+
+    format!("{:x}", Sha256::digest(content.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn backup_names_tell_same_named_projects_apart() {
+        let now = chrono::Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+
+        assert_eq!(
+            backup_file_name("Checkout Redesign!", "p1", now),
+            "checkout-redesign-20240501T120000Z-p1.json"
+        );
+        assert_ne!(
+            backup_file_name("Tracker", "p1", now),
+            backup_file_name("Tracker", "p2", now)
+        );
+        assert_eq!(
+            backup_file_name("***", "p1", now),
+            "project-20240501T120000Z-p1.json"
+        );
+    }
+}
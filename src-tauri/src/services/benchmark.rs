@@ -0,0 +1,143 @@
+//! Times every installed chat model on the same prompt.
+//!
+//! Each model is unloaded first, answers once cold and once warm, and is unloaded again by the
+//! warm run, so every model starts from the same state and they never compete for memory.
+
+use super::ollama::{ChatMessage, ChatOverrides, OllamaService, TimedChat};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PROMPT: &str = "Describe, in about 150 words, the core features a small team \
+                                  would need from a shared task tracker.";
+/// Caps each reply so one verbose model cannot dominate the run.
+const MAX_TOKENS: u32 = 256;
+/// Fixed so repeated runs ask every model for the same work.
+const SEED: i64 = 42;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub model: String,
+    /// Time Ollama spent loading the model for the cold run.
+    pub load_ms: Option<u64>,
+    /// First-token latency on the cold run, including the load.
+    pub cold_first_token_ms: Option<u64>,
+    /// First-token latency and total time on the warm run.
+    pub first_token_ms: Option<u64>,
+    pub total_ms: Option<u64>,
+    /// Generation speed on the warm run, from Ollama's own counters.
+    pub tokens_per_second: Option<f64>,
+    /// Set when the model could not be benchmarked; the timings are then `None`.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub prompt: String,
+    /// Fastest first, with failed models last.
+    pub results: Vec<BenchmarkResult>,
+}
+
+/// Emitted as `benchmark-progress` after each model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub result: BenchmarkResult,
+}
+
+/// Benchmarks the installed chat models one after another, calling `on_result` as each one
+/// finishes. Embedding-only models are skipped.
+pub async fn run<F>(
+    ollama: &OllamaService,
+    prompt: &str,
+    mut on_result: F,
+) -> Result<BenchmarkReport, String>
+where
+    F: FnMut(BenchmarkProgress),
+{
+    let mut models = Vec::new();
+    for model in ollama.list_models().await? {
+        let embedding_only = ollama
+            .model_info(&model)
+            .await
+            .is_ok_and(|info| info.embedding_only);
+        if !embedding_only {
+            models.push(model);
+        }
+    }
+
+    let mut results = Vec::new();
+    for (i, model) in models.iter().enumerate() {
+        let result = match benchmark_model(ollama, model, prompt).await {
+            Ok(result) => result,
+            Err(e) => BenchmarkResult {
+                model: model.clone(),
+                load_ms: None,
+                cold_first_token_ms: None,
+                first_token_ms: None,
+                total_ms: None,
+                tokens_per_second: None,
+                error: Some(e),
+            },
+        };
+
+        on_result(BenchmarkProgress {
+            completed: i + 1,
+            total: models.len(),
+            result: result.clone(),
+        });
+        results.push(result);
+    }
+
+    results.sort_by(|a, b| match (a.tokens_per_second, b.tokens_per_second) {
+        (Some(a), Some(b)) => b.total_cmp(&a),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.model.cmp(&b.model),
+    });
+
+    Ok(BenchmarkReport {
+        prompt: prompt.to_string(),
+        results,
+    })
+}
+
+async fn benchmark_model(
+    ollama: &OllamaService,
+    model: &str,
+    prompt: &str,
+) -> Result<BenchmarkResult, String> {
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt.to_string(),
+        images: Vec::new(),
+    }];
+    let overrides = ChatOverrides {
+        model: Some(model.to_string()),
+        seed: Some(SEED),
+        temperature: Some(0.0),
+        max_tokens: Some(MAX_TOKENS),
+        ..Default::default()
+    };
+
+    ollama.unload(model).await?;
+    let cold = ollama
+        .timed_chat(messages.clone(), &overrides, None)
+        .await?;
+    let warm = ollama.timed_chat(messages, &overrides, Some(0)).await?;
+
+    Ok(BenchmarkResult {
+        model: model.to_string(),
+        load_ms: cold.load_duration.map(|d| d.as_millis() as u64),
+        cold_first_token_ms: cold.first_token.map(|d| d.as_millis() as u64),
+        first_token_ms: warm.first_token.map(|d| d.as_millis() as u64),
+        total_ms: Some(warm.total.as_millis() as u64),
+        tokens_per_second: tokens_per_second(&warm),
+        error: None,
+    })
+}
+
+fn tokens_per_second(run: &TimedChat) -> Option<f64> {
+    let tokens = run.eval_count?;
+    let seconds = run.eval_duration?.as_secs_f64();
+    (seconds > 0.0).then(|| tokens as f64 / seconds)
+}
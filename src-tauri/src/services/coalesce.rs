@@ -0,0 +1,113 @@
+//! Batches streamed deltas so fast models do not flood the IPC bridge with one event per token.
+
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_INTERVAL_MS: u64 = 50;
+pub const DEFAULT_MAX_CHUNKS: usize = 32;
+
+/// Collects deltas and releases them together once `interval` has passed since the last release
+/// or `max_chunks` deltas are waiting. Nothing is dropped: `flush` hands back whatever is left
+/// when the stream ends.
+pub struct ChunkCoalescer {
+    interval: Duration,
+    max_chunks: usize,
+    pending: String,
+    pending_chunks: usize,
+    last_emit: Instant,
+}
+
+impl ChunkCoalescer {
+    /// A zero interval or a `max_chunks` of 1 releases every delta as it arrives.
+    pub fn new(interval: Duration, max_chunks: usize) -> Self {
+        Self {
+            interval,
+            max_chunks: max_chunks.max(1),
+            pending: String::new(),
+            pending_chunks: 0,
+            last_emit: Instant::now(),
+        }
+    }
+
+    /// Adds a delta, returning the batch to emit if one is due. The cadence is only checked as
+    /// deltas arrive, so a stalled stream holds its last batch until the next delta or `flush`.
+    pub fn push(&mut self, delta: &str) -> Option<String> {
+        self.pending.push_str(delta);
+        self.pending_chunks += 1;
+
+        if self.pending_chunks >= self.max_chunks || self.last_emit.elapsed() >= self.interval {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    pub fn flush(&mut self) -> Option<String> {
+        self.pending_chunks = 0;
+        self.last_emit = Instant::now();
+
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.pending))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELTAS: [&str; 7] = ["The ", "tracker ", "needs ", "tags", ", ", "dué ", "dates"];
+
+    /// Pushes every delta, then flushes, returning the batches released along the way.
+    fn batches(coalescer: &mut ChunkCoalescer, deltas: &[&str]) -> Vec<String> {
+        let mut batches: Vec<String> = deltas
+            .iter()
+            .filter_map(|delta| coalescer.push(delta))
+            .collect();
+        batches.extend(coalescer.flush());
+        batches
+    }
+
+    #[test]
+    fn full_batches_are_released_by_count() {
+        let mut coalescer = ChunkCoalescer::new(Duration::from_secs(3600), 3);
+
+        let batches = batches(&mut coalescer, &DELTAS);
+
+        assert_eq!(
+            batches,
+            ["The tracker needs ", "tags, dué ", "dates"].map(String::from)
+        );
+        assert_eq!(batches.concat(), DELTAS.concat());
+        assert_eq!(coalescer.flush(), None);
+    }
+
+    #[test]
+    fn batches_are_released_once_the_interval_passes() {
+        let interval = Duration::from_millis(50);
+        let mut coalescer = ChunkCoalescer::new(interval, DEFAULT_MAX_CHUNKS);
+        let mut batches = Vec::new();
+
+        for (i, delta) in DELTAS.iter().enumerate() {
+            if i == 3 {
+                std::thread::sleep(interval);
+            }
+            batches.extend(coalescer.push(delta));
+        }
+        batches.extend(coalescer.flush());
+
+        assert_eq!(
+            batches,
+            ["The tracker needs tags", ", dué dates"].map(String::from)
+        );
+        assert_eq!(batches.concat(), DELTAS.concat());
+    }
+
+    #[test]
+    fn a_zero_interval_releases_every_delta() {
+        let mut coalescer = ChunkCoalescer::new(Duration::ZERO, DEFAULT_MAX_CHUNKS);
+
+        assert_eq!(batches(&mut coalescer, &DELTAS), DELTAS.map(String::from));
+    }
+}
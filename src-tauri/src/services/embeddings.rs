@@ -0,0 +1,50 @@
+//! Storage format for message embeddings: vectors are kept as little-endian `f32` blobs.
+
+/// Number of earlier messages `send_message` cites when asked for sources.
+pub const CITATION_LIMIT: usize = 3;
+
+/// Messages less similar to the reply than this are never cited.
+pub const CITATION_MIN_SIMILARITY: f32 = 0.5;
+
+pub fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+pub fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Cosine similarity, or `None` for vectors of different lengths or zero magnitude.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() {
+        return None;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let magnitude = norm(a) * norm(b);
+
+    (magnitude > 0.0).then(|| dot / magnitude)
+}
+
+/// Ids of the `CITATION_LIMIT` candidates most similar to `target`, best first, ignoring
+/// those below `CITATION_MIN_SIMILARITY`.
+pub fn top_sources(target: &[f32], candidates: &[(String, Vec<f32>)]) -> Vec<String> {
+    let mut scored: Vec<(f32, &String)> = candidates
+        .iter()
+        .filter_map(|(id, vector)| Some((cosine_similarity(target, vector)?, id)))
+        .filter(|(score, _)| *score >= CITATION_MIN_SIMILARITY)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    scored
+        .into_iter()
+        .take(CITATION_LIMIT)
+        .map(|(_, id)| id.clone())
+        .collect()
+}
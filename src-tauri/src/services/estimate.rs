@@ -0,0 +1,62 @@
+//! Rough token and time estimates for a planned batch, so a long run can be caught before it
+//! starts.
+
+use super::latency::LatencyStats;
+use serde::{Deserialize, Serialize};
+
+/// Generation speed assumed when no chat call has been timed yet. Deliberately low, so the
+/// first estimate on an unknown machine errs long rather than short.
+pub const DEFAULT_TOKENS_PER_SECOND: f64 = 10.0;
+/// Reply length assumed when no output limit is configured.
+pub const DEFAULT_REPLY_TOKENS: u32 = 400;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEstimate {
+    /// Chat calls the batch makes: every prompt, `generations` times.
+    pub calls: u64,
+    /// Estimated input tokens of each prompt, in the order given.
+    pub prompt_tokens: Vec<u64>,
+    /// Input tokens summed over every call.
+    pub total_input_tokens: u64,
+    /// Most output tokens the batch can produce, `None` when replies are not capped.
+    pub max_output_tokens: Option<u64>,
+    /// Typical duration of one call: the median of recent calls, or the default rate's guess.
+    pub per_call_ms: u64,
+    pub estimated_duration_ms: u64,
+    /// Duration if every call is as slow as the 90th percentile of recent calls.
+    pub slow_duration_ms: u64,
+    /// Recent calls the projection is based on; 0 means the default rate was used.
+    pub latency_samples: usize,
+}
+
+/// Projects a batch that sends each of `prompt_tokens` `generations` times, with replies capped
+/// at `max_reply_tokens` if set.
+pub fn estimate(
+    prompt_tokens: Vec<u64>,
+    generations: u32,
+    max_reply_tokens: Option<u32>,
+    latency: &LatencyStats,
+) -> BatchEstimate {
+    let generations = u64::from(generations);
+    let calls = prompt_tokens.len() as u64 * generations;
+
+    let (per_call_ms, slow_call_ms) = match (latency.p50_ms, latency.p90_ms) {
+        (Some(p50), Some(p90)) => (u64::from(p50), u64::from(p90)),
+        _ => {
+            let reply_tokens = max_reply_tokens.unwrap_or(DEFAULT_REPLY_TOKENS);
+            let ms = (f64::from(reply_tokens) / DEFAULT_TOKENS_PER_SECOND * 1000.0) as u64;
+            (ms, ms)
+        }
+    };
+
+    BatchEstimate {
+        calls,
+        total_input_tokens: prompt_tokens.iter().sum::<u64>() * generations,
+        prompt_tokens,
+        max_output_tokens: max_reply_tokens.map(|tokens| u64::from(tokens) * calls),
+        per_call_ms,
+        estimated_duration_ms: per_call_ms * calls,
+        slow_duration_ms: slow_call_ms * calls,
+        latency_samples: latency.count,
+    }
+}
@@ -0,0 +1,235 @@
+use super::limits;
+use super::prompt::ResponseFormat;
+use super::workflow;
+use crate::database::models::{AssistantMetadata, Message, Project, ProjectExport};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// Column order of `write_csv`. Append new columns at the end so existing spreadsheets keep
+/// working.
+const CSV_COLUMNS: [&str; 7] = [
+    "timestamp",
+    "conversation_id",
+    "phase",
+    "role",
+    "token_count",
+    "model",
+    "char_count",
+];
+
+/// Renders a project as a Markdown document: metadata header, requirements checklist, then
+/// each conversation's transcript. Replies generated in plain-text format are fenced so their
+/// content survives verbatim.
+pub fn render_markdown(export: &ProjectExport) -> String {
+    let mut out = render_header(&export.project);
+
+    if !export.requirements.is_empty() {
+        out.push_str("## Requirements\n\n");
+        for requirement in &export.requirements {
+            let checked = if requirement.status == "done" {
+                "x"
+            } else {
+                " "
+            };
+            out.push_str(&format!(
+                "- [{}] {} ({}, {})\n",
+                checked, requirement.text, requirement.priority, requirement.status
+            ));
+        }
+        out.push('\n');
+    }
+
+    for entry in &export.conversations {
+        out.push_str(&format!(
+            "## Conversation: {} ({})\n\n",
+            entry.conversation.phase, entry.conversation.created_at
+        ));
+
+        for message in &entry.messages {
+            if message.role == "system" || !message.selected {
+                continue;
+            }
+
+            let speaker = if message.role == "user" {
+                "User"
+            } else {
+                "Assistant"
+            };
+            out.push_str(&format!("**{}:**\n\n", speaker));
+
+            if message_format(message.metadata.as_deref()) == ResponseFormat::Plain {
+                out.push_str(&format!("```text\n{}\n```\n\n", message.content));
+            } else {
+                out.push_str(&format!("{}\n\n", message.content));
+            }
+        }
+    }
+
+    out
+}
+
+/// Names printed before each message of a plain transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeakerLabels {
+    pub user: String,
+    pub assistant: String,
+}
+
+impl Default for SpeakerLabels {
+    fn default() -> Self {
+        Self {
+            user: "User".to_string(),
+            assistant: "Assistant".to_string(),
+        }
+    }
+}
+
+/// One `Label: content` line per selected user and assistant message, with no markup or
+/// metadata. Continuation lines of a multi-line message are indented to line up with its first.
+pub fn render_plain(messages: &[Message], labels: &SpeakerLabels) -> String {
+    let mut lines = Vec::new();
+
+    for message in messages {
+        if message.role == "system" || !message.selected {
+            continue;
+        }
+
+        let label = if message.role == "user" {
+            &labels.user
+        } else {
+            &labels.assistant
+        };
+        let prefix = format!("{}: ", label);
+        let indent = " ".repeat(prefix.chars().count());
+
+        let mut content = message.content.trim().lines();
+        lines.push(format!("{}{}", prefix, content.next().unwrap_or_default()));
+        for line in content {
+            if line.trim().is_empty() {
+                lines.push(String::new());
+            } else {
+                lines.push(format!("{}{}", indent, line));
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Writes one CSV row per message, in conversation order, after a header row. Token counts are
+/// estimates. `model` is the one recorded on an assistant reply, falling back to the model the
+/// conversation started on; it is empty for other roles.
+pub fn write_csv<W: Write>(export: &ProjectExport, writer: W) -> Result<W, String> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(CSV_COLUMNS).map_err(|e| e.to_string())?;
+
+    for entry in &export.conversations {
+        let conversation = &entry.conversation;
+
+        for message in &entry.messages {
+            let model = if message.role == "assistant" {
+                message
+                    .metadata
+                    .as_deref()
+                    .and_then(|m| serde_json::from_str::<AssistantMetadata>(m).ok())
+                    .and_then(|m| m.model)
+                    .or_else(|| conversation.created_with_model.clone())
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
+
+            csv.write_record([
+                message.created_at.as_str(),
+                conversation.id.as_str(),
+                message.phase.as_deref().unwrap_or(&conversation.phase),
+                message.role.as_str(),
+                &limits::estimate_tokens(&message.content).to_string(),
+                &model,
+                &message.content.chars().count().to_string(),
+            ])
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    csv.into_inner().map_err(|e| e.to_string())
+}
+
+/// Renders the project header followed by one section per phase, in workflow order. Phases
+/// without content are left out.
+pub fn render_decisions(project: &Project, sections: &[(&str, String)]) -> String {
+    let mut out = render_header(project);
+    out.push_str("## Decisions\n\n");
+
+    if sections.is_empty() {
+        out.push_str("_No decisions recorded yet._\n");
+    }
+    for (phase, body) in sections {
+        out.push_str(&format!("### {}\n\n{}\n\n", phase, body.trim()));
+    }
+
+    out
+}
+
+/// Selected user and assistant messages grouped by phase, in workflow order. A message is
+/// filed under its own phase, or its conversation's if it has none.
+pub fn messages_by_phase(
+    export: &ProjectExport,
+    pinned_only: bool,
+) -> Vec<(&'static str, Vec<&Message>)> {
+    workflow::PHASES
+        .iter()
+        .map(|phase| {
+            let messages = export
+                .conversations
+                .iter()
+                .flat_map(|entry| {
+                    entry.messages.iter().filter(move |message| {
+                        message
+                            .phase
+                            .as_deref()
+                            .unwrap_or(&entry.conversation.phase)
+                            == *phase
+                    })
+                })
+                .filter(|message| message.role != "system" && message.selected)
+                .filter(|message| message.pinned || !pinned_only)
+                .collect::<Vec<_>>();
+            (*phase, messages)
+        })
+        .filter(|(_, messages)| !messages.is_empty())
+        .collect()
+}
+
+pub fn decisions_prompt(phase: &str) -> String {
+    format!(
+        "The following is the '{}' phase of a product specification discussion. List the \
+         decisions and requirements it settled as concise Markdown bullet points, one per \
+         decision. Leave out open questions, alternatives that were rejected and small talk. \
+         Reply with ONLY the list.",
+        phase
+    )
+}
+
+fn render_header(project: &Project) -> String {
+    let mut out = format!("# {}\n\n{}\n\n", project.name, project.description);
+
+    if let Some(industry) = &project.industry {
+        out.push_str(&format!("**Industry:** {}\n\n", industry));
+    }
+    if let Some(audience) = &project.target_audience {
+        out.push_str(&format!("**Target audience:** {}\n\n", audience));
+    }
+    out.push_str(&format!("**Status:** {}\n\n", project.status));
+
+    out
+}
+
+fn message_format(metadata: Option<&str>) -> ResponseFormat {
+    metadata
+        .and_then(|m| serde_json::from_str::<AssistantMetadata>(m).ok())
+        .and_then(|m| m.format)
+        .and_then(|f| ResponseFormat::parse(&f).ok())
+        .unwrap_or_default()
+}
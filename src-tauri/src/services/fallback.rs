@@ -0,0 +1,162 @@
+//! Falling back to smaller models when the requested one cannot be loaded.
+
+use std::collections::VecDeque;
+
+/// Fragments of the errors Ollama reports when a model cannot be loaded or runs out of memory.
+/// Anything else, such as a bad request or an unknown model, fails the turn as before.
+const RECOVERABLE_ERRORS: [&str; 7] = [
+    "out of memory",
+    "requires more system memory",
+    "unable to allocate",
+    "failed to load model",
+    "error loading model",
+    "llama runner process has terminated",
+    "model runner has unexpectedly stopped",
+];
+
+/// Whether `error` means the model could not be loaded, so another model might still answer.
+pub fn is_recoverable(error: &str) -> bool {
+    let error = error.to_lowercase();
+    RECOVERABLE_ERRORS
+        .iter()
+        .any(|fragment| error.contains(fragment))
+}
+
+/// The models still to try after the requested one, and what went wrong with each model tried.
+pub struct FallbackChain {
+    requested: String,
+    remaining: VecDeque<String>,
+    failures: Vec<(String, String)>,
+}
+
+impl FallbackChain {
+    /// Tries `fallbacks` in order after `requested`, skipping duplicates and `requested` itself.
+    pub fn new(requested: &str, fallbacks: &[String]) -> Self {
+        let mut remaining = VecDeque::new();
+        for model in fallbacks {
+            let model = model.trim();
+            if !model.is_empty() && model != requested && !remaining.iter().any(|m| m == model) {
+                remaining.push_back(model.to_string());
+            }
+        }
+
+        Self {
+            requested: requested.to_string(),
+            remaining,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Records that `model` failed with `error` and returns the next model to try. Errors that
+    /// are not load failures are returned as they are, without trying further models.
+    pub fn fail(&mut self, model: &str, error: String) -> Result<String, String> {
+        if !is_recoverable(&error) {
+            return Err(error);
+        }
+        self.skip(model, error)
+    }
+
+    /// Like `fail`, but moves on whatever the error, for a fallback that could not even be
+    /// selected. Once no models are left, the error names every model that was tried.
+    pub fn skip(&mut self, model: &str, error: String) -> Result<String, String> {
+        self.failures.push((model.to_string(), error));
+        self.remaining.pop_front().ok_or_else(|| self.exhausted())
+    }
+
+    fn exhausted(&self) -> String {
+        let Some(((_, first), rest)) = self.failures.split_first() else {
+            return format!("Model failed to load: {}", self.requested);
+        };
+        if rest.is_empty() {
+            return first.clone();
+        }
+
+        let fallbacks: Vec<String> = rest
+            .iter()
+            .map(|(model, error)| format!("{}: {}", model, error))
+            .collect();
+        format!(
+            "{}; every fallback model failed as well ({})",
+            first,
+            fallbacks.join("; ")
+        )
+    }
+}
+
+/// Shown with a reply that came from a fallback model, whose style may differ.
+pub fn warning(requested: &str, answered: &str) -> String {
+    format!(
+        "{} could not be loaded, so {} answered instead.",
+        requested, answered
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OOM: &str = "model requires more system memory (8 GiB) than is available";
+
+    fn chain(fallbacks: &[&str]) -> FallbackChain {
+        let fallbacks: Vec<String> = fallbacks.iter().map(|model| model.to_string()).collect();
+        FallbackChain::new("big", &fallbacks)
+    }
+
+    #[test]
+    fn load_failures_move_down_the_chain_without_repeats() {
+        let mut chain = chain(&["medium", " big ", "", "medium", "small"]);
+
+        assert_eq!(chain.fail("big", OOM.to_string()).unwrap(), "medium");
+        assert_eq!(chain.fail("medium", OOM.to_string()).unwrap(), "small");
+    }
+
+    #[test]
+    fn other_errors_fail_the_turn_at_once() {
+        let mut chain = chain(&["small"]);
+
+        assert_eq!(
+            chain.fail("big", "model 'big' not found".to_string()),
+            Err("model 'big' not found".to_string())
+        );
+    }
+
+    #[test]
+    fn skipped_fallbacks_move_on_whatever_the_error() {
+        let mut chain = chain(&["missing", "small"]);
+
+        assert_eq!(chain.fail("big", OOM.to_string()).unwrap(), "missing");
+        assert_eq!(
+            chain
+                .skip("missing", "Model not found: missing".to_string())
+                .unwrap(),
+            "small"
+        );
+    }
+
+    #[test]
+    fn an_exhausted_chain_names_every_failure() {
+        let mut chain = chain(&["small"]);
+        chain.fail("big", OOM.to_string()).unwrap();
+
+        assert_eq!(
+            chain.fail("small", "failed to load model".to_string()),
+            Err(format!(
+                "{}; every fallback model failed as well (small: failed to load model)",
+                OOM
+            ))
+        );
+    }
+
+    #[test]
+    fn without_fallbacks_the_original_error_stands() {
+        let mut chain = chain(&[]);
+
+        assert_eq!(chain.fail("big", OOM.to_string()), Err(OOM.to_string()));
+    }
+
+    #[test]
+    fn recoverable_errors_are_matched_case_insensitively() {
+        assert!(is_recoverable("CUDA error: Out Of Memory"));
+        assert!(!is_recoverable("invalid request"));
+    }
+}
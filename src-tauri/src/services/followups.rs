@@ -0,0 +1,61 @@
+use super::structured;
+
+pub const MIN_SUGGESTIONS: usize = 3;
+pub const MAX_SUGGESTIONS: usize = 5;
+
+/// Appended as a final user turn; the reply is never stored.
+pub fn prompt(phase: &str) -> String {
+    format!(
+        "Suggest {} to {} concise follow-up questions I could ask next to move this \
+         specification forward. Keep them relevant to the '{}' phase and to what has not been \
+         covered yet. Reply with ONLY a JSON array of strings.",
+        MIN_SUGGESTIONS, MAX_SUGGESTIONS, phase
+    )
+}
+
+/// Reads the questions from a JSON array of strings, or failing that one per line with list
+/// markers and quotes stripped. Blank and repeated entries are dropped and at most
+/// `MAX_SUGGESTIONS` are kept.
+pub fn parse(response: &str) -> Vec<String> {
+    let candidates = structured::parse_json::<Vec<String>>(response).unwrap_or_else(|_| {
+        response
+            .lines()
+            .map(strip_list_marker)
+            // Skip fences, brackets and lead-ins such as "Here are some questions:".
+            .filter(|line| {
+                !line.starts_with("```") && !matches!(*line, "[" | "]") && !line.ends_with(':')
+            })
+            .map(str::to_string)
+            .collect()
+    });
+
+    let mut questions: Vec<String> = Vec::new();
+    for candidate in candidates {
+        let question = candidate
+            .trim()
+            .trim_end_matches(',')
+            .trim_matches(['"', '\''])
+            .trim()
+            .to_string();
+        if !question.is_empty() && !questions.contains(&question) {
+            questions.push(question);
+        }
+    }
+
+    questions.truncate(MAX_SUGGESTIONS);
+    questions
+}
+
+/// Strips a leading `-`, `*`, `•`, `1.` or `1)` marker.
+fn strip_list_marker(line: &str) -> &str {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix(['-', '*', '•']) {
+        return rest.trim_start();
+    }
+
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match line[digits..].strip_prefix(['.', ')']) {
+        Some(rest) if digits > 0 => rest.trim_start(),
+        _ => line,
+    }
+}
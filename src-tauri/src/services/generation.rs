@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Tracks in-flight generations, one per conversation (or per project for an embedding run), so
+/// they can be refused while one is running and cancelled by id.
+#[derive(Default)]
+pub struct GenerationRegistry {
+    active: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl GenerationRegistry {
+    /// Registers a generation for the conversation. The returned guard removes the entry when
+    /// dropped, so every exit path (success, error, cancel) cleans up the map.
+    pub fn start(&self, conversation_id: &str) -> Result<ActiveGeneration<'_>, String> {
+        let mut active = self.active.lock().map_err(|e| e.to_string())?;
+
+        if active.contains_key(conversation_id) {
+            return Err(format!(
+                "A generation is already running for conversation: {}",
+                conversation_id
+            ));
+        }
+
+        let token = CancellationToken::new();
+        active.insert(conversation_id.to_string(), token.clone());
+
+        Ok(ActiveGeneration {
+            registry: self,
+            conversation_id: conversation_id.to_string(),
+            token,
+        })
+    }
+
+    pub fn cancel(&self, conversation_id: &str) -> Result<bool, String> {
+        let active = self.active.lock().map_err(|e| e.to_string())?;
+
+        match active.get(conversation_id) {
+            Some(token) => {
+                token.cancel();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn is_running(&self, conversation_id: &str) -> Result<bool, String> {
+        let active = self.active.lock().map_err(|e| e.to_string())?;
+        Ok(active.contains_key(conversation_id))
+    }
+
+    /// Cancels every in-flight generation, returning how many were signalled.
+    pub fn cancel_all(&self) -> Result<usize, String> {
+        let active = self.active.lock().map_err(|e| e.to_string())?;
+
+        for token in active.values() {
+            token.cancel();
+        }
+
+        Ok(active.len())
+    }
+}
+
+pub struct ActiveGeneration<'a> {
+    registry: &'a GenerationRegistry,
+    conversation_id: String,
+    pub token: CancellationToken,
+}
+
+impl Drop for ActiveGeneration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.registry.active.lock() {
+            active.remove(&self.conversation_id);
+        }
+    }
+}
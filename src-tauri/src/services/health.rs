@@ -0,0 +1,45 @@
+use super::ollama::OllamaService;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+
+/// Owns the shutdown signal for the background Ollama health poller.
+#[derive(Default)]
+pub struct HealthMonitor {
+    shutdown: CancellationToken,
+}
+
+impl HealthMonitor {
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// Shortest polling interval, so a zero interval saved before it was rejected cannot turn the
+/// poller into a busy loop.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls Ollama every `interval` and emits `ollama-status` whenever reachability or model
+/// availability changes, including once for the initial status.
+pub async fn poll(app: AppHandle, interval: Duration, shutdown: CancellationToken) {
+    let interval = interval.max(MIN_INTERVAL);
+    let mut last = None;
+
+    loop {
+        let status = app.state::<OllamaService>().status().await;
+
+        if last.as_ref() != Some(&status) {
+            let _ = app.emit("ollama-status", &status);
+            last = Some(status);
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
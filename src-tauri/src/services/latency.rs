@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyStats {
+    /// Calls currently in the window, at most its capacity.
+    pub count: usize,
+    pub capacity: usize,
+    /// Percentiles in milliseconds, `None` before the first call.
+    pub p50_ms: Option<u32>,
+    pub p90_ms: Option<u32>,
+    pub p99_ms: Option<u32>,
+}
+
+/// Largest accepted window. The buffer is allocated up front, so an absurd setting would
+/// otherwise abort the app at every launch.
+pub const MAX_SAMPLES: usize = 10_000;
+
+/// Durations of the most recent completed chat calls, in milliseconds. Once full, each new
+/// sample replaces the oldest.
+pub struct LatencyTracker {
+    capacity: usize,
+    samples: Mutex<VecDeque<u32>>,
+}
+
+impl LatencyTracker {
+    /// `capacity` is clamped to between 1 and `MAX_SAMPLES`.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.clamp(1, MAX_SAMPLES);
+        Self {
+            capacity,
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, duration: Duration) {
+        let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+        if let Ok(mut samples) = self.samples.lock() {
+            if samples.len() == self.capacity {
+                samples.pop_front();
+            }
+            samples.push_back(millis);
+        }
+    }
+
+    pub fn reset(&self) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.clear();
+        }
+    }
+
+    pub fn stats(&self) -> Result<LatencyStats, String> {
+        let mut sorted: Vec<u32> = self
+            .samples
+            .lock()
+            .map_err(|e| e.to_string())?
+            .iter()
+            .copied()
+            .collect();
+        sorted.sort_unstable();
+
+        Ok(LatencyStats {
+            count: sorted.len(),
+            capacity: self.capacity,
+            p50_ms: percentile(&sorted, 50),
+            p90_ms: percentile(&sorted, 90),
+            p99_ms: percentile(&sorted, 99),
+        })
+    }
+}
+
+/// Nearest-rank percentile of already sorted samples.
+fn percentile(sorted: &[u32], percent: usize) -> Option<u32> {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_is_clamped() {
+        assert_eq!(LatencyTracker::new(0).stats().unwrap().capacity, 1);
+        assert_eq!(
+            LatencyTracker::new(usize::MAX).stats().unwrap().capacity,
+            MAX_SAMPLES
+        );
+    }
+
+    #[test]
+    fn oldest_samples_are_dropped_once_full() {
+        let tracker = LatencyTracker::new(2);
+        for millis in [100, 200, 300] {
+            tracker.record(Duration::from_millis(millis));
+        }
+
+        let stats = tracker.stats().unwrap();
+        assert_eq!(stats.count, 2);
+        assert_eq!((stats.p50_ms, stats.p99_ms), (Some(200), Some(300)));
+    }
+}
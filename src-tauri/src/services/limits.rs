@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Rough characters-per-token ratio for English prose with Llama-family tokenizers.
+const CHARS_PER_TOKEN: u64 = 4;
+
+/// Soft thresholds past which a conversation is likely to degrade. Exceeding them only produces
+/// a warning; sending is never blocked.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ConversationLimits {
+    pub max_messages: u64,
+    pub max_tokens: u64,
+    /// Most recent messages `compress_conversation` leaves verbatim by default.
+    #[serde(default = "default_keep_recent")]
+    pub keep_recent: u64,
+}
+
+impl Default for ConversationLimits {
+    fn default() -> Self {
+        Self {
+            max_messages: 100,
+            max_tokens: 24_000,
+            keep_recent: default_keep_recent(),
+        }
+    }
+}
+
+fn default_keep_recent() -> u64 {
+    20
+}
+
+impl ConversationLimits {
+    pub fn warning(&self, message_count: u64, token_estimate: u64) -> Option<String> {
+        let reason = if token_estimate > self.max_tokens {
+            format!(
+                "about {} tokens, above the suggested {}",
+                token_estimate, self.max_tokens
+            )
+        } else if message_count > self.max_messages {
+            format!(
+                "{} messages, above the suggested {}",
+                message_count, self.max_messages
+            )
+        } else {
+            return None;
+        };
+
+        Some(format!(
+            "This conversation has grown to {}. Responses may lose track of earlier context; \
+             consider summarizing it or starting a fresh conversation.",
+            reason
+        ))
+    }
+}
+
+/// Runtime-adjustable conversation limits, shared as Tauri state.
+#[derive(Default)]
+pub struct LimitSettings {
+    limits: Mutex<ConversationLimits>,
+}
+
+impl LimitSettings {
+    pub fn new(limits: ConversationLimits) -> Self {
+        Self {
+            limits: Mutex::new(limits),
+        }
+    }
+
+    pub fn get(&self) -> Result<ConversationLimits, String> {
+        Ok(*self.limits.lock().map_err(|e| e.to_string())?)
+    }
+
+    pub fn set(&self, limits: ConversationLimits) -> Result<(), String> {
+        if limits.max_messages == 0 || limits.max_tokens == 0 {
+            return Err("Conversation limits must be greater than zero".to_string());
+        }
+
+        *self.limits.lock().map_err(|e| e.to_string())? = limits;
+        Ok(())
+    }
+}
+
+/// Estimates the token count of `text`, rounding up so short messages still count.
+pub fn estimate_tokens(text: &str) -> u64 {
+    tokens_for_chars(text.chars().count() as u64)
+}
+
+pub fn tokens_for_chars(chars: u64) -> u64 {
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
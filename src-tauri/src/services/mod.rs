@@ -1 +1,25 @@
+pub mod archive;
+pub mod benchmark;
+pub mod coalesce;
+pub mod embeddings;
+pub mod estimate;
+pub mod export;
+pub mod fallback;
+pub mod followups;
+pub mod generation;
+pub mod health;
+pub mod latency;
+pub mod limits;
 pub mod ollama;
+pub mod prompt;
+pub mod recommend;
+pub mod redact;
+pub mod requirements;
+pub mod review;
+pub mod rolling_summary;
+pub mod sanitize;
+pub mod structured;
+pub mod template;
+pub mod timestamps;
+pub mod title;
+pub mod workflow;
@@ -1,21 +1,55 @@
-use reqwest::Client;
+use super::latency::{LatencyStats, LatencyTracker};
+use futures_util::StreamExt;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 
-const OLLAMA_BASE_URL: &str = "http://localhost:11434";
+const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+const CONNECTION_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Prefix of the error `ensure_available` returns, so callers can tell it apart.
+pub const UNAVAILABLE_ERROR: &str = "OllamaUnavailable";
+/// `done_reason` for a reply cut at a stop sequence, whether by Ollama or locally.
+const DONE_REASON_STOP: &str = "stop";
+/// First Ollama release with the `/api/embed` endpoint.
+const EMBED_MIN_VERSION: (u64, u64, u64) = (0, 3, 0);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OllamaConfig {
+    /// Where the Ollama API listens.
+    pub base_url: String,
     pub model: String,
+    /// Model used by `embed`; must be an embedding model such as `nomic-embed-text`.
+    pub embedding_model: String,
     pub temperature: f32,
+    /// Fixed sampling seed for every request that does not set its own.
+    pub seed: Option<i64>,
     pub max_tokens: Option<u32>,
+    /// Sequences that end a generation. The stop text itself is never part of the reply.
+    pub stop: Option<Vec<String>>,
+    /// Whether `send_message` streams replies. Can be changed at runtime.
+    pub streaming: bool,
+    pub health_check_interval_secs: u64,
+    /// Chat calls kept for `latency_stats`.
+    pub latency_samples: usize,
 }
 
 impl Default for OllamaConfig {
     fn default() -> Self {
         Self {
+            base_url: DEFAULT_BASE_URL.to_string(),
             model: "llama3.1:8b".to_string(),
+            embedding_model: "nomic-embed-text".to_string(),
             temperature: 0.7,
+            seed: None,
             max_tokens: Some(4096),
+            stop: None,
+            streaming: true,
+            health_check_interval_secs: 15,
+            latency_samples: 256,
         }
     }
 }
@@ -24,6 +58,9 @@ impl Default for OllamaConfig {
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
+    /// Base64-encoded images for vision models. Left out of the request when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -32,73 +69,1192 @@ struct ChatRequest {
     messages: Vec<ChatMessage>,
     stream: bool,
     options: ChatOptions,
+    /// Seconds the model stays loaded afterwards; the server default when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<u32>,
 }
 
-#[derive(Debug, Serialize)]
-struct ChatOptions {
-    temperature: f32,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatOptions {
+    pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    num_predict: Option<u32>,
+    pub num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+/// Per-request departures from the service config. `None` keeps the configured value or the
+/// server default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatOverrides {
+    pub model: Option<String>,
+    pub seed: Option<i64>,
+    pub num_ctx: Option<u32>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ChatResponse {
     pub message: ChatMessage,
     pub done: bool,
+    /// Why generation ended (`stop`, `length`, `load`, ...), sent with the final chunk. Older
+    /// Ollama versions omit it.
+    #[serde(default)]
+    pub done_reason: Option<String>,
+    /// Generated tokens and the time spent on them and on loading the model, in nanoseconds.
+    /// Sent with the final chunk.
+    #[serde(default)]
+    pub eval_count: Option<u64>,
+    #[serde(default)]
+    pub eval_duration: Option<u64>,
+    #[serde(default)]
+    pub load_duration: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub model: String,
+    pub context_length: Option<u64>,
+    pub parameter_size: Option<String>,
+    pub quantization: Option<String>,
+    pub family: Option<String>,
+    /// Whether the model accepts images alongside the prompt.
+    #[serde(default)]
+    pub vision: bool,
+    /// Whether the model only produces embeddings and cannot chat.
+    #[serde(default)]
+    pub embedding_only: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ShowRequest<'a> {
+    model: &'a str,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ShowDetails {
+    parameter_size: Option<String>,
+    quantization_level: Option<String>,
+    family: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShowResponse {
+    #[serde(default)]
+    details: ShowDetails,
+    #[serde(default)]
+    model_info: HashMap<String, serde_json::Value>,
+    /// Reported by Ollama 0.6 and later, e.g. `["completion", "vision"]`.
+    #[serde(default)]
+    capabilities: Option<Vec<String>>,
+    /// Present on older releases for models with an image encoder, such as LLaVA.
+    #[serde(default)]
+    projector_info: Option<serde_json::Value>,
+}
+
+impl ShowResponse {
+    fn vision(&self) -> bool {
+        match &self.capabilities {
+            Some(capabilities) => capabilities.iter().any(|c| c == "vision"),
+            None => self.projector_info.is_some(),
+        }
+    }
+
+    /// Without a capabilities list, falls back to the BERT family and the usual `embed` naming.
+    fn embedding_only(&self, model: &str) -> bool {
+        match &self.capabilities {
+            Some(capabilities) => !capabilities.iter().any(|c| c == "completion"),
+            None => {
+                self.details
+                    .family
+                    .as_deref()
+                    .is_some_and(|family| family.contains("bert"))
+                    || model.contains("embed")
+            }
+        }
+    }
+
+    /// Context length lives under an architecture-prefixed key such as `llama.context_length`.
+    fn context_length(&self) -> Option<u64> {
+        let architecture = self
+            .model_info
+            .get("general.architecture")
+            .and_then(|v| v.as_str());
+
+        architecture
+            .and_then(|arch| self.model_info.get(&format!("{}.context_length", arch)))
+            .or_else(|| {
+                self.model_info
+                    .iter()
+                    .find(|(key, _)| key.ends_with(".context_length"))
+                    .map(|(_, value)| value)
+            })
+            .and_then(|v| v.as_u64())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    /// Required, so an unrelated JSON service on the port is not taken for Ollama.
+    models: Vec<TagModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagModel {
+    name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Reachable,
+    ConnectionRefused,
+    Timeout,
+    Unauthorized,
+    /// Something answered, but not with an Ollama tags response.
+    WrongService,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionResponse {
+    version: String,
+}
+
+/// What the backend can do, so the UI can hide features it does not support.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub backend: String,
+    pub connection: ConnectionStatus,
+    pub version: Option<String>,
+    pub streaming: bool,
+    /// The streaming preference, applied only when the backend supports streaming.
+    pub streaming_enabled: bool,
+    pub embeddings: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OllamaStatus {
+    pub reachable: bool,
+    pub model: String,
+    pub model_available: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct UnloadRequest<'a> {
+    model: &'a str,
+    keep_alive: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamStatus {
+    Done,
+    Cancelled,
+}
+
+/// Exact request and raw response body of a generation, captured only in debug mode.
+#[derive(Debug, Clone)]
+pub struct ChatTrace {
+    pub request: String,
+    pub response: String,
+}
+
+#[derive(Debug)]
+pub struct ChatOutput {
+    pub content: String,
+    pub done_reason: Option<String>,
+    pub trace: Option<ChatTrace>,
+}
+
+/// Timings of a reply generated by `timed_chat`. Server-reported values are `None` when the
+/// backend does not send them.
+#[derive(Debug)]
+pub struct TimedChat {
+    /// Until the first non-empty delta, including any model load.
+    pub first_token: Option<Duration>,
+    pub total: Duration,
+    pub eval_count: Option<u64>,
+    pub eval_duration: Option<Duration>,
+    pub load_duration: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct StreamOutcome {
+    pub status: StreamStatus,
+    /// `None` when cancelled or when the server did not report one.
+    pub done_reason: Option<String>,
+    pub trace: Option<ChatTrace>,
 }
 
 pub struct OllamaService {
     client: Client,
     config: OllamaConfig,
+    model_info_cache: Mutex<HashMap<String, ModelInfo>>,
+    debug_mode: AtomicBool,
+    /// Starts as `config.model` and can be changed at runtime.
+    model: Mutex<String>,
+    /// Starts as `config.streaming` and can be changed at runtime.
+    streaming: AtomicBool,
+    /// Starts as `config.stop` and can be changed at runtime.
+    stop: Mutex<Option<Vec<String>>>,
+    /// When reachability was last checked, by the health poller or `check_connection`.
+    reachability: Mutex<Option<(Instant, bool)>>,
+    latency: LatencyTracker,
 }
 
 impl OllamaService {
     pub fn new(config: OllamaConfig) -> Self {
         Self {
             client: Client::new(),
+            model: Mutex::new(config.model.clone()),
+            stop: Mutex::new(config.stop.clone()),
+            streaming: AtomicBool::new(config.streaming),
+            latency: LatencyTracker::new(config.latency_samples),
             config,
+            model_info_cache: Mutex::new(HashMap::new()),
+            debug_mode: AtomicBool::new(false),
+            reachability: Mutex::new(None),
+        }
+    }
+
+    pub fn stop_sequences(&self) -> Result<Option<Vec<String>>, String> {
+        Ok(self.stop.lock().map_err(|e| e.to_string())?.clone())
+    }
+
+    /// Replaces the stop sequences; `None` or an empty list disables them.
+    pub fn set_stop_sequences(&self, stop: Option<Vec<String>>) -> Result<(), String> {
+        let stop = stop.filter(|stop| !stop.is_empty());
+        if stop.iter().flatten().any(|sequence| sequence.is_empty()) {
+            return Err("Stop sequences cannot be empty".to_string());
         }
+
+        *self.stop.lock().map_err(|e| e.to_string())? = stop;
+        self.latency.reset();
+        Ok(())
+    }
+
+    pub fn set_debug_mode(&self, enabled: bool) {
+        self.debug_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn debug_mode(&self) -> bool {
+        self.debug_mode.load(Ordering::Relaxed)
+    }
+
+    pub fn default_model(&self) -> String {
+        self.model
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Replaces the model used when a request does not name one.
+    pub fn set_default_model(&self, model: String) {
+        *self
+            .model
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = model;
+        self.latency.reset();
+    }
+
+    pub fn embedding_model(&self) -> &str {
+        &self.config.embedding_model
+    }
+
+    /// The server version, or "unknown" when it cannot be fetched.
+    pub async fn version_or_unknown(&self) -> String {
+        self.version()
+            .await
+            .unwrap_or_else(|_| "unknown".to_string())
+    }
+
+    pub fn set_streaming_enabled(&self, enabled: bool) {
+        self.streaming.store(enabled, Ordering::Relaxed);
+        self.latency.reset();
+    }
+
+    /// Latency percentiles of recent successful chat calls, measured from sending the request
+    /// to the last byte of the reply. Cleared whenever the runtime config changes, so samples
+    /// are comparable.
+    pub fn latency_stats(&self) -> Result<LatencyStats, String> {
+        self.latency.stats()
+    }
+
+    pub fn streaming_enabled(&self) -> bool {
+        self.streaming.load(Ordering::Relaxed)
     }
 
     pub async fn chat(&self, messages: Vec<ChatMessage>) -> Result<String, String> {
+        Ok(self.chat_traced(messages).await?.content)
+    }
+
+    /// Like `chat`, but also returns the serialized request and raw response body when debug
+    /// mode is on.
+    pub async fn chat_traced(&self, messages: Vec<ChatMessage>) -> Result<ChatOutput, String> {
+        self.chat_with(messages, &ChatOverrides::default()).await
+    }
+
+    /// Like `chat_traced`, with the model and sampling options adjusted by `overrides`.
+    pub async fn chat_with(
+        &self,
+        messages: Vec<ChatMessage>,
+        overrides: &ChatOverrides,
+    ) -> Result<ChatOutput, String> {
+        let request = self.chat_request(messages, false, overrides)?;
+        let request_json = self.trace_request(&request)?;
+        let started = Instant::now();
+
+        let response = self
+            .client
+            .post(format!("{}/api/chat", self.config.base_url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(api_error(response).await);
+        }
+
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+
+        let chat_response: ChatResponse =
+            serde_json::from_str(&body).map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        // Not every backend honours `stop`, so cut at the first sequence here as well.
+        let mut content = chat_response.message.content;
+        let mut done_reason = chat_response.done_reason;
+        if let Some(at) = find_stop(
+            &content,
+            request.options.stop.as_deref().unwrap_or_default(),
+        ) {
+            content.truncate(at);
+            done_reason = Some(DONE_REASON_STOP.to_string());
+        }
+        self.latency.record(started.elapsed());
+
+        Ok(ChatOutput {
+            content,
+            done_reason,
+            trace: request_json.map(|request| ChatTrace {
+                request,
+                response: body,
+            }),
+        })
+    }
+
+    /// Streams a chat completion, calling `on_chunk` with each content delta as it arrives.
+    /// Returns early with `StreamStatus::Cancelled` once `cancel` fires; whatever was already
+    /// passed to `on_chunk` is the partial response.
+    pub async fn chat_stream<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        overrides: &ChatOverrides,
+        cancel: &CancellationToken,
+        mut on_chunk: F,
+    ) -> Result<StreamOutcome, String>
+    where
+        F: FnMut(&str) -> Result<(), String>,
+    {
+        let request = self.chat_request(messages, true, overrides)?;
+        let request_json = self.trace_request(&request)?;
+        let mut raw_response = request_json.as_ref().map(|_| String::new());
+        let mut stop_filter = StopFilter::new(request.options.stop.as_deref().unwrap_or_default());
+        let mut done_reason = None;
+        let started = Instant::now();
+
+        let send = self
+            .client
+            .post(format!("{}/api/chat", self.config.base_url))
+            .json(&request)
+            .send();
+
+        let status = 'stream: {
+            let response = tokio::select! {
+                biased;
+                _ = cancel.cancelled() => break 'stream StreamStatus::Cancelled,
+                response = send => response.map_err(|e| format!("Failed to send request: {}", e))?,
+            };
+
+            if !response.status().is_success() {
+                return Err(api_error(response).await);
+            }
+
+            let mut stream = response.bytes_stream();
+            let mut buffer: Vec<u8> = Vec::new();
+
+            loop {
+                let chunk = tokio::select! {
+                    biased;
+                    _ = cancel.cancelled() => break 'stream StreamStatus::Cancelled,
+                    chunk = stream.next() => chunk,
+                };
+
+                let Some(chunk) = chunk else {
+                    return Err("Stream ended before Ollama reported completion".to_string());
+                };
+                buffer.extend_from_slice(
+                    &chunk.map_err(|e| format!("Failed to read stream: {}", e))?,
+                );
+
+                while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=newline).collect();
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+
+                    if let Some(raw) = raw_response.as_mut() {
+                        raw.push_str(&String::from_utf8_lossy(&line));
+                    }
+
+                    let chat_response: ChatResponse = serde_json::from_slice(&line)
+                        .map_err(|e| format!("Failed to parse stream chunk: {}", e))?;
+
+                    let (emit, stopped) = stop_filter.push(&chat_response.message.content);
+                    if !emit.is_empty() {
+                        on_chunk(&emit)?;
+                    }
+
+                    if stopped {
+                        done_reason = Some(DONE_REASON_STOP.to_string());
+                        break 'stream StreamStatus::Done;
+                    }
+                    if chat_response.done {
+                        done_reason = chat_response.done_reason;
+                        break 'stream StreamStatus::Done;
+                    }
+                }
+            }
+        };
+
+        let held_back = stop_filter.finish();
+        if !held_back.is_empty() {
+            on_chunk(&held_back)?;
+        }
+
+        // A cancelled stream says nothing about how long the reply would have taken.
+        if status == StreamStatus::Done {
+            self.latency.record(started.elapsed());
+        }
+
+        Ok(StreamOutcome {
+            status,
+            done_reason,
+            trace: request_json
+                .zip(raw_response)
+                .map(|(request, response)| ChatTrace { request, response }),
+        })
+    }
+
+    /// Streams a reply only to time it; the content is discarded and nothing is recorded in the
+    /// latency stats. A `keep_alive` of `Some(0)` unloads the model as soon as the reply is done.
+    pub async fn timed_chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        overrides: &ChatOverrides,
+        keep_alive: Option<u32>,
+    ) -> Result<TimedChat, String> {
         let request = ChatRequest {
-            model: self.config.model.clone(),
-            messages,
-            stream: false,
-            options: ChatOptions {
-                temperature: self.config.temperature,
-                num_predict: self.config.max_tokens,
-            },
+            keep_alive,
+            ..self.chat_request(messages, true, overrides)?
         };
+        let started = Instant::now();
 
         let response = self
             .client
-            .post(format!("{}/api/chat", OLLAMA_BASE_URL))
+            .post(format!("{}/api/chat", self.config.base_url))
             .json(&request)
             .send()
             .await
             .map_err(|e| format!("Failed to send request: {}", e))?;
 
+        if !response.status().is_success() {
+            return Err(api_error(response).await);
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut first_token = None;
+
+        while let Some(chunk) = stream.next().await {
+            buffer.extend_from_slice(&chunk.map_err(|e| format!("Failed to read stream: {}", e))?);
+
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+
+                let chat_response: ChatResponse = serde_json::from_slice(&line)
+                    .map_err(|e| format!("Failed to parse stream chunk: {}", e))?;
+                if first_token.is_none() && !chat_response.message.content.is_empty() {
+                    first_token = Some(started.elapsed());
+                }
+
+                if chat_response.done {
+                    return Ok(TimedChat {
+                        first_token,
+                        total: started.elapsed(),
+                        eval_count: chat_response.eval_count,
+                        eval_duration: chat_response.eval_duration.map(Duration::from_nanos),
+                        load_duration: chat_response.load_duration.map(Duration::from_nanos),
+                    });
+                }
+            }
+        }
+
+        Err("Stream ended before Ollama reported completion".to_string())
+    }
+
+    /// The model and options a request with `overrides` is sent with.
+    pub fn resolve_options(
+        &self,
+        overrides: &ChatOverrides,
+    ) -> Result<(String, ChatOptions), String> {
+        let model = overrides
+            .model
+            .clone()
+            .unwrap_or_else(|| self.default_model());
+
+        let options = ChatOptions {
+            temperature: overrides.temperature.unwrap_or(self.config.temperature),
+            num_predict: overrides.max_tokens.or(self.config.max_tokens),
+            seed: overrides.seed.or(self.config.seed),
+            num_ctx: overrides.num_ctx,
+            top_p: overrides.top_p,
+            stop: self.stop_sequences()?,
+        };
+
+        Ok((model, options))
+    }
+
+    fn chat_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        stream: bool,
+        overrides: &ChatOverrides,
+    ) -> Result<ChatRequest, String> {
+        let (model, options) = self.resolve_options(overrides)?;
+
+        Ok(ChatRequest {
+            model,
+            messages,
+            stream,
+            options,
+            keep_alive: None,
+        })
+    }
+
+    fn trace_request(&self, request: &ChatRequest) -> Result<Option<String>, String> {
+        if !self.debug_mode() {
+            return Ok(None);
+        }
+
+        serde_json::to_string(request)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Probes `/api/tags` and classifies the outcome so callers can tell a stopped server
+    /// apart from a proxy rejecting credentials or an unrelated service on the port.
+    pub async fn check_connection(&self) -> Result<ConnectionStatus, String> {
+        let status = self.probe_connection().await;
+        self.record_reachability(matches!(status, Ok(ConnectionStatus::Reachable)));
+        status
+    }
+
+    /// Fails with an `UNAVAILABLE_ERROR` error when Ollama cannot be reached. The last recorded
+    /// reachability is trusted while the health poller would still be on its current cycle, so
+    /// this only makes a request when that is stale.
+    pub async fn ensure_available(&self) -> Result<(), String> {
+        let ttl =
+            Duration::from_secs(self.config.health_check_interval_secs) + CONNECTION_CHECK_TIMEOUT;
+        let cached = self
+            .reachability
+            .lock()
+            .map_err(|e| e.to_string())?
+            .filter(|(checked_at, _)| checked_at.elapsed() < ttl)
+            .map(|(_, reachable)| reachable);
+
+        let reachable = match cached {
+            Some(reachable) => reachable,
+            None => matches!(
+                self.check_connection().await,
+                Ok(ConnectionStatus::Reachable)
+            ),
+        };
+
+        if reachable {
+            Ok(())
+        } else {
+            Err(format!(
+                "{}: Ollama is not reachable at {}. Make sure it is running and try again.",
+                UNAVAILABLE_ERROR, self.config.base_url
+            ))
+        }
+    }
+
+    fn record_reachability(&self, reachable: bool) {
+        if let Ok(mut last) = self.reachability.lock() {
+            *last = Some((Instant::now(), reachable));
+        }
+    }
+
+    async fn probe_connection(&self) -> Result<ConnectionStatus, String> {
+        let response = match self
+            .client
+            .get(format!("{}/api/tags", self.config.base_url))
+            .timeout(CONNECTION_CHECK_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) if e.is_timeout() => return Ok(ConnectionStatus::Timeout),
+            Err(e) if e.is_connect() => return Ok(ConnectionStatus::ConnectionRefused),
+            Err(e) => return Err(format!("Failed to connect to Ollama: {}", e)),
+        };
+
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Ok(ConnectionStatus::Unauthorized);
+        }
+        if !status.is_success() {
+            return Ok(ConnectionStatus::WrongService);
+        }
+
+        match response.json::<TagsResponse>().await {
+            Ok(_) => Ok(ConnectionStatus::Reachable),
+            Err(e) if e.is_timeout() => Ok(ConnectionStatus::Timeout),
+            Err(_) => Ok(ConnectionStatus::WrongService),
+        }
+    }
+
+    /// Embeds all `texts` in a single `/api/embed` request. Vectors come back in input order.
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let response = self
+            .client
+            .post(format!("{}/api/embed", self.config.base_url))
+            .json(&EmbedRequest {
+                model: &self.config.embedding_model,
+                input: texts,
+            })
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+
         if !response.status().is_success() {
             return Err(format!("Ollama API error: {}", response.status()));
         }
 
-        let chat_response: ChatResponse = response
+        let embed: EmbedResponse = response
             .json()
             .await
             .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-        Ok(chat_response.message.content)
+        if embed.embeddings.len() != texts.len() {
+            return Err(format!(
+                "Ollama returned {} embeddings for {} inputs",
+                embed.embeddings.len(),
+                texts.len()
+            ));
+        }
+
+        Ok(embed.embeddings)
     }
 
-    pub async fn check_connection(&self) -> Result<bool, String> {
+    pub async fn version(&self) -> Result<String, String> {
         let response = self
             .client
-            .get(format!("{}/api/tags", OLLAMA_BASE_URL))
+            .get(format!("{}/api/version", self.config.base_url))
+            .timeout(CONNECTION_CHECK_TIMEOUT)
             .send()
             .await
             .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
 
-        Ok(response.status().is_success())
+        if !response.status().is_success() {
+            return Err(format!("Ollama API error: {}", response.status()));
+        }
+
+        let version: VersionResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        Ok(version.version)
+    }
+
+    /// Probes the server and derives its features from the reported version. An unreachable
+    /// server reports no features rather than an error.
+    pub async fn capabilities(&self) -> Result<Capabilities, String> {
+        let connection = self.check_connection().await?;
+        let reachable = connection == ConnectionStatus::Reachable;
+        let version = if reachable {
+            self.version().await.ok()
+        } else {
+            None
+        };
+
+        // Every Ollama release streams `/api/chat`.
+        let streaming = reachable;
+        let embeddings = version
+            .as_deref()
+            .and_then(parse_version)
+            .is_some_and(|version| version >= EMBED_MIN_VERSION);
+
+        Ok(Capabilities {
+            backend: "ollama".to_string(),
+            connection,
+            version,
+            streaming,
+            streaming_enabled: streaming && self.streaming_enabled(),
+            embeddings,
+        })
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<String>, String> {
+        let response = self
+            .client
+            .get(format!("{}/api/tags", self.config.base_url))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to connect to Ollama: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Ollama API error: {}", response.status()));
+        }
+
+        let tags: TagsResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+
+    /// Reports reachability and whether the configured model is installed. Never fails; an
+    /// unreachable server is reported as such.
+    pub async fn status(&self) -> OllamaStatus {
+        let models = self.list_models().await;
+        self.record_reachability(models.is_ok());
+
+        OllamaStatus {
+            reachable: models.is_ok(),
+            model: self.default_model(),
+            model_available: models.is_ok_and(|models| self.has_default_model(&models)),
+        }
+    }
+
+    /// Whether the configured model is among the installed `models`, allowing for the implicit
+    /// `:latest` tag.
+    pub fn has_default_model(&self, models: &[String]) -> bool {
+        let model = self.default_model();
+        models
+            .iter()
+            .any(|name| *name == model || *name == format!("{}:latest", model))
+    }
+
+    pub async fn model_info(&self, model: &str) -> Result<ModelInfo, String> {
+        if let Some(info) = self
+            .model_info_cache
+            .lock()
+            .map_err(|e| e.to_string())?
+            .get(model)
+        {
+            return Ok(info.clone());
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/api/show", self.config.base_url))
+            .json(&ShowRequest { model })
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Ollama API error: {}", response.status()));
+        }
+
+        let show: ShowResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        let info = ModelInfo {
+            model: model.to_string(),
+            vision: show.vision(),
+            embedding_only: show.embedding_only(model),
+            context_length: show.context_length(),
+            parameter_size: show.details.parameter_size,
+            quantization: show.details.quantization_level,
+            family: show.details.family,
+        };
+
+        self.model_info_cache
+            .lock()
+            .map_err(|e| e.to_string())?
+            .insert(model.to_string(), info.clone());
+
+        Ok(info)
+    }
+
+    /// Asks Ollama to evict the configured model from memory immediately.
+    pub async fn unload_model(&self) -> Result<(), String> {
+        self.unload(&self.default_model()).await
+    }
+
+    /// Asks Ollama to evict `model` from memory immediately. Unloading a model that is not
+    /// loaded succeeds.
+    pub async fn unload(&self, model: &str) -> Result<(), String> {
+        let response = self
+            .client
+            .post(format!("{}/api/generate", self.config.base_url))
+            .json(&UnloadRequest {
+                model,
+                keep_alive: 0,
+            })
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Ollama API error: {}", response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// The error for a failed chat request, with Ollama's own message when the body has one: the
+/// status alone does not tell a model that failed to load from any other server error.
+async fn api_error(response: reqwest::Response) -> String {
+    let status = response.status();
+    match response.json::<ErrorResponse>().await {
+        Ok(body) => format!("Ollama API error: {}: {}", status, body.error),
+        Err(_) => format!("Ollama API error: {}", status),
+    }
+}
+
+/// Holds back the tail of a stream that could be the start of a stop sequence, so a sequence
+/// split across chunks is still caught before any of it is emitted.
+struct StopFilter<'a> {
+    stops: &'a [String],
+    /// Longest tail, in bytes, that cannot yet contain a whole stop sequence.
+    hold: usize,
+    pending: String,
+}
+
+impl<'a> StopFilter<'a> {
+    fn new(stops: &'a [String]) -> Self {
+        Self {
+            stops,
+            hold: stops
+                .iter()
+                .map(|stop| stop.len())
+                .max()
+                .unwrap_or_default()
+                .saturating_sub(1),
+            pending: String::new(),
+        }
+    }
+
+    /// Returns the text that is safe to emit, and whether a stop sequence was reached. After a
+    /// stop, nothing more should be pushed.
+    fn push(&mut self, delta: &str) -> (String, bool) {
+        self.pending.push_str(delta);
+
+        if let Some(at) = find_stop(&self.pending, self.stops) {
+            self.pending.truncate(at);
+            return (std::mem::take(&mut self.pending), true);
+        }
+
+        let mut split = self.pending.len().saturating_sub(self.hold);
+        while !self.pending.is_char_boundary(split) {
+            split -= 1;
+        }
+        let tail = self.pending.split_off(split);
+
+        (std::mem::replace(&mut self.pending, tail), false)
+    }
+
+    fn finish(self) -> String {
+        self.pending
+    }
+}
+
+/// Byte offset of the earliest stop sequence in `text`.
+fn find_stop(text: &str, stops: &[String]) -> Option<usize> {
+    stops
+        .iter()
+        .filter_map(|stop| text.find(stop.as_str()))
+        .min()
+}
+
+/// Parses `major.minor.patch`, ignoring any pre-release suffix such as `-rc1`.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim_start_matches('v').split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().flatten().unwrap_or(0),
+    ))
+}
+
+/// A stand-in Ollama server for tests.
+#[cfg(test)]
+pub mod mock {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    /// Answers each request with the status and JSON body routed to its path, or 404 for any
+    /// other path, and returns the server's base URL. It runs until the test process exits.
+    pub fn serve(routes: Vec<(&'static str, u16, String)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                respond(stream, &routes);
+            }
+        });
+        base_url
+    }
+
+    /// Accepts connections but never answers them, and returns the server's base URL.
+    pub fn hang() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let open: Vec<TcpStream> = listener.incoming().flatten().collect();
+            drop(open);
+        });
+        base_url
+    }
+
+    fn respond(mut stream: TcpStream, routes: &[(&'static str, u16, String)]) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 || header == "\r\n" {
+                break;
+            }
+            if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+        let mut body = vec![0; content_length];
+        let _ = reader.read_exact(&mut body);
+
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let (status, body) = routes
+            .iter()
+            .find(|(route, ..)| *route == path)
+            .map_or((404, ""), |(_, status, body)| (*status, body.as_str()));
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::async_runtime::block_on;
+
+    fn status_at(base_url: String) -> ConnectionStatus {
+        let ollama = OllamaService::new(OllamaConfig {
+            base_url,
+            ..OllamaConfig::default()
+        });
+        block_on(ollama.check_connection()).unwrap()
+    }
+
+    fn status_for(status: u16, body: &str) -> ConnectionStatus {
+        status_at(mock::serve(vec![("/api/tags", status, body.to_string())]))
+    }
+
+    #[test]
+    fn tells_connection_failures_apart() {
+        assert_eq!(
+            status_for(200, r#"{"models":[]}"#),
+            ConnectionStatus::Reachable
+        );
+        assert_eq!(status_for(401, ""), ConnectionStatus::Unauthorized);
+        assert_eq!(status_for(403, ""), ConnectionStatus::Unauthorized);
+        assert_eq!(status_for(200, "{}"), ConnectionStatus::WrongService);
+        assert_eq!(status_for(404, ""), ConnectionStatus::WrongService);
+        assert_eq!(status_at(mock::hang()), ConnectionStatus::Timeout);
+
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        assert_eq!(status_at(base_url), ConnectionStatus::ConnectionRefused);
+    }
+
+    fn stops(stops: &[&str]) -> Vec<String> {
+        stops.iter().map(|stop| stop.to_string()).collect()
+    }
+
+    /// Pushes `chunks` through a filter, returning what was emitted and whether it stopped.
+    fn filter(stops: &[String], chunks: &[&str]) -> (String, bool) {
+        let mut filter = StopFilter::new(stops);
+        let mut emitted = String::new();
+        for chunk in chunks {
+            let (emit, stopped) = filter.push(chunk);
+            emitted.push_str(&emit);
+            if stopped {
+                return (emitted, true);
+            }
+        }
+        emitted.push_str(&filter.finish());
+        (emitted, false)
+    }
+
+    #[test]
+    fn stop_sequences_are_caught_across_chunks() {
+        let end = stops(&["<END>"]);
+
+        assert_eq!(
+            filter(&end, &["Hello <E", "N", "D> ignored"]),
+            ("Hello ".to_string(), true)
+        );
+        assert_eq!(filter(&end, &["<END>"]), (String::new(), true));
+        assert_eq!(filter(&end, &["Done", "<END>"]), ("Done".to_string(), true));
+    }
+
+    #[test]
+    fn text_that_only_looks_like_a_stop_is_kept() {
+        let end = stops(&["<END>"]);
+
+        assert_eq!(
+            filter(&end, &["a <EN", "dless> b <"]),
+            ("a <ENdless> b <".to_string(), false)
+        );
+        assert_eq!(
+            filter(&[], &["no ", "stops"]),
+            ("no stops".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn the_earliest_stop_wins() {
+        assert_eq!(
+            filter(&stops(&["###", "\n\n"]), &["one\n\ntwo ### three"]),
+            ("one".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn held_back_text_splits_on_character_boundaries() {
+        let mut filter = StopFilter::new(&[]);
+        let end = stops(&["ende"]);
+        let mut held = StopFilter::new(&end);
+
+        assert_eq!(filter.push("héé"), ("héé".to_string(), false));
+        // Three bytes are held back, which would split the first two-byte "é", so both stay.
+        assert_eq!(held.push("aéé"), ("a".to_string(), false));
+        assert_eq!(held.finish(), "éé");
+    }
+
+    #[test]
+    fn latency_restarts_with_each_config_change() {
+        let service = OllamaService::new(OllamaConfig::default());
+        let changes: [&dyn Fn(); 3] = [
+            &|| service.set_default_model("qwen2.5:7b".to_string()),
+            &|| service.set_streaming_enabled(false),
+            &|| service.set_stop_sequences(None).unwrap(),
+        ];
+
+        for change in changes {
+            service.latency.record(Duration::from_millis(120));
+            change();
+            assert_eq!(service.latency_stats().unwrap().count, 0);
+        }
+    }
+
+    #[test]
+    fn done_reason_is_optional() {
+        let parse = |json: &str| serde_json::from_str::<ChatResponse>(json).unwrap();
+
+        let current = parse(
+            r#"{"message":{"role":"assistant","content":"Hi"},"done":true,"done_reason":"length"}"#,
+        );
+        assert_eq!(current.done_reason.as_deref(), Some("length"));
+        // Older Ollama versions leave the field out.
+        let older = parse(r#"{"message":{"role":"assistant","content":"Hi"},"done":true}"#);
+        assert_eq!(older.done_reason, None);
+    }
+
+    #[test]
+    fn chat_replies_report_why_they_ended() {
+        let base_url = mock::serve(vec![(
+            "/api/chat",
+            200,
+            r#"{"message":{"role":"assistant","content":"Cut"},"done":true,"done_reason":"length"}"#
+                .to_string(),
+        )]);
+        let ollama = OllamaService::new(OllamaConfig {
+            base_url,
+            ..OllamaConfig::default()
+        });
+
+        let output = block_on(ollama.chat_with(Vec::new(), &ChatOverrides::default())).unwrap();
+        assert_eq!(output.done_reason.as_deref(), Some("length"));
+    }
+
+    #[test]
+    fn chat_replies_end_before_the_stop_sequence() {
+        let base_url = mock::serve(vec![(
+            "/api/chat",
+            200,
+            r#"{"message":{"role":"assistant","content":"Answer.<END> more"},"done":true}"#
+                .to_string(),
+        )]);
+        let ollama = OllamaService::new(OllamaConfig {
+            base_url,
+            stop: Some(stops(&["<END>"])),
+            ..OllamaConfig::default()
+        });
+
+        let output = block_on(ollama.chat_with(Vec::new(), &ChatOverrides::default())).unwrap();
+        assert_eq!(output.content, "Answer.");
+        assert_eq!(output.done_reason.as_deref(), Some(DONE_REASON_STOP));
     }
 }
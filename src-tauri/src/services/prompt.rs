@@ -0,0 +1,158 @@
+use super::workflow;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Markdown,
+    Plain,
+}
+
+impl ResponseFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "markdown" => Ok(Self::Markdown),
+            "plain" => Ok(Self::Plain),
+            other => Err(format!("Unknown response format: {}", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Plain => "plain",
+        }
+    }
+
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            Self::Markdown => "Format your replies using Markdown.",
+            Self::Plain => {
+                "Respond in plain text only. Do not use Markdown syntax such as headings, \
+                 bullet markers, emphasis, tables, or code fences."
+            }
+        }
+    }
+}
+
+/// Longest accepted language name, to keep the instruction from becoming a prompt of its own.
+pub const MAX_LANGUAGE_LEN: usize = 64;
+
+pub fn normalize_language(language: Option<String>) -> Result<Option<String>, String> {
+    let Some(language) = language.map(|l| l.trim().to_string()) else {
+        return Ok(None);
+    };
+
+    if language.is_empty() {
+        return Ok(None);
+    }
+    if language.chars().count() > MAX_LANGUAGE_LEN {
+        return Err(format!(
+            "Language must be at most {} characters",
+            MAX_LANGUAGE_LEN
+        ));
+    }
+
+    Ok(Some(language))
+}
+
+pub fn language_instruction(language: &str) -> String {
+    format!(
+        "Write your replies in {}, regardless of the language the user writes in.",
+        language
+    )
+}
+
+/// Longest accepted persona. Personas describe a voice, not a second set of instructions.
+pub const MAX_PERSONA_LEN: usize = 2000;
+
+/// Trims `persona`, treating a blank one as unset.
+pub fn normalize_persona(persona: Option<String>) -> Result<Option<String>, String> {
+    let Some(persona) = persona.map(|p| p.trim().to_string()) else {
+        return Ok(None);
+    };
+
+    if persona.is_empty() {
+        return Ok(None);
+    }
+    if persona.chars().count() > MAX_PERSONA_LEN {
+        return Err(format!(
+            "Persona must be at most {} characters",
+            MAX_PERSONA_LEN
+        ));
+    }
+
+    Ok(Some(persona))
+}
+
+/// The layers that make up the system prompt for a turn. They are always rendered in this
+/// order: persona, phase instruction, response format, output language. Later layers are the
+/// more specific ones, so a phase instruction or language takes precedence over anything the
+/// persona says about the same thing. The rolling summary, when there is one, follows as a
+/// separate system message.
+pub struct SystemPrompt<'a> {
+    /// The project's persona, or the global one.
+    pub persona: Option<&'a str>,
+    pub phase: &'a str,
+    pub format: ResponseFormat,
+    pub language: Option<&'a str>,
+}
+
+impl SystemPrompt<'_> {
+    pub fn render(&self) -> String {
+        let mut sections: Vec<String> = self.persona.map(str::to_string).into_iter().collect();
+        sections.push(workflow::phase_instruction(self.phase));
+        sections.push(self.format.instruction().to_string());
+
+        if let Some(language) = self.language {
+            sections.push(language_instruction(language));
+        }
+
+        sections.join("\n\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_prompt_layers_run_from_persona_to_language() {
+        let prompt = SystemPrompt {
+            persona: Some("You are a patient business analyst."),
+            phase: "consultation",
+            format: ResponseFormat::Plain,
+            language: Some("German"),
+        }
+        .render();
+
+        let sections = [
+            "You are a patient business analyst.".to_string(),
+            workflow::phase_instruction("consultation"),
+            ResponseFormat::Plain.instruction().to_string(),
+            language_instruction("German"),
+        ];
+        assert_eq!(prompt, sections.join("\n\n"));
+    }
+
+    #[test]
+    fn system_prompt_leaves_out_unset_layers() {
+        let prompt = SystemPrompt {
+            persona: None,
+            phase: "generation",
+            format: ResponseFormat::Markdown,
+            language: None,
+        }
+        .render();
+
+        assert_eq!(
+            prompt,
+            format!(
+                "{}\n\n{}",
+                workflow::phase_instruction("generation"),
+                ResponseFormat::Markdown.instruction()
+            )
+        );
+    }
+}
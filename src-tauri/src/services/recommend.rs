@@ -0,0 +1,174 @@
+//! Picks a default chat model from the installed ones, for first runs where none is configured.
+
+use super::ollama::{ModelInfo, OllamaService};
+use serde::{Deserialize, Serialize};
+
+/// Parameter counts, in billions, that run acceptably on typical hardware while still following
+/// the interview reliably.
+const IDEAL_MIN_BILLIONS: f64 = 7.0;
+const IDEAL_MAX_BILLIONS: f64 = 14.0;
+/// Contexts long enough to hold a full phase without summarizing.
+const LONG_CONTEXT: u64 = 32_768;
+const USABLE_CONTEXT: u64 = 8_192;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelRecommendation {
+    /// Best-ranked model, `None` when no installed model can chat.
+    pub recommended: Option<String>,
+    /// Chat models, best first.
+    pub ranked: Vec<RankedModel>,
+    /// Installed models left out of the ranking, with why.
+    pub excluded: Vec<ExcludedModel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedModel {
+    pub model: String,
+    pub score: f64,
+    pub parameter_size: Option<String>,
+    pub context_length: Option<u64>,
+    /// One line per factor behind the score, for the UI to show.
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExcludedModel {
+    pub model: String,
+    pub reason: String,
+}
+
+/// Ranks every installed model. Fails only when the model list itself cannot be fetched.
+pub async fn recommend(ollama: &OllamaService) -> Result<ModelRecommendation, String> {
+    let mut infos = Vec::new();
+    let mut excluded = Vec::new();
+    for model in ollama.list_models().await? {
+        match ollama.model_info(&model).await {
+            Ok(info) => infos.push(info),
+            Err(e) => excluded.push(ExcludedModel {
+                model,
+                reason: format!("Details unavailable: {}", e),
+            }),
+        }
+    }
+
+    let mut recommendation = rank(infos);
+    recommendation.excluded.extend(excluded);
+    Ok(recommendation)
+}
+
+pub fn rank(models: Vec<ModelInfo>) -> ModelRecommendation {
+    let mut ranked = Vec::new();
+    let mut excluded = Vec::new();
+
+    for info in models {
+        if info.embedding_only {
+            excluded.push(ExcludedModel {
+                model: info.model,
+                reason: "Embedding model; it cannot chat".to_string(),
+            });
+            continue;
+        }
+
+        let mut reasons = Vec::new();
+        let size_score = size_score(info.parameter_size.as_deref(), &mut reasons);
+        let context_score = context_score(info.context_length, &mut reasons);
+        ranked.push(RankedModel {
+            model: info.model,
+            score: size_score + context_score,
+            parameter_size: info.parameter_size,
+            context_length: info.context_length,
+            reasons,
+        });
+    }
+
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.model.cmp(&b.model))
+    });
+
+    ModelRecommendation {
+        recommended: ranked.first().map(|model| model.model.clone()),
+        ranked,
+        excluded,
+    }
+}
+
+/// Up to 1.0, highest inside the ideal range and falling off on either side of it.
+fn size_score(parameter_size: Option<&str>, reasons: &mut Vec<String>) -> f64 {
+    let Some(billions) = parameter_size.and_then(parse_billions) else {
+        reasons.push("Parameter count unknown".to_string());
+        return 0.5;
+    };
+
+    if billions < IDEAL_MIN_BILLIONS {
+        reasons.push(format!(
+            "{} parameters: fast, but may lose track of a long interview",
+            format_billions(billions)
+        ));
+        0.4 + 0.6 * billions / IDEAL_MIN_BILLIONS
+    } else if billions > IDEAL_MAX_BILLIONS {
+        reasons.push(format!(
+            "{} parameters: capable, but slow on most machines",
+            format_billions(billions)
+        ));
+        (IDEAL_MAX_BILLIONS / billions).max(0.2)
+    } else {
+        reasons.push(format!(
+            "{} parameters: a good balance of speed and quality",
+            format_billions(billions)
+        ));
+        1.0
+    }
+}
+
+fn context_score(context_length: Option<u64>, reasons: &mut Vec<String>) -> f64 {
+    match context_length {
+        Some(tokens) if tokens >= LONG_CONTEXT => {
+            reasons.push(format!("{}-token context holds a whole phase", tokens));
+            0.2
+        }
+        Some(tokens) if tokens >= USABLE_CONTEXT => {
+            reasons.push(format!("{}-token context", tokens));
+            0.1
+        }
+        Some(tokens) => {
+            reasons.push(format!(
+                "{}-token context needs frequent summarizing",
+                tokens
+            ));
+            0.0
+        }
+        None => {
+            reasons.push("Context length unknown".to_string());
+            0.05
+        }
+    }
+}
+
+/// Parses Ollama's `parameter_size`, such as `8.0B` or `137M`, into billions.
+fn parse_billions(size: &str) -> Option<f64> {
+    let size = size.trim();
+    let (number, scale) = match size.chars().last()?.to_ascii_uppercase() {
+        'B' => (&size[..size.len() - 1], 1.0),
+        'M' => (&size[..size.len() - 1], 0.001),
+        'K' => (&size[..size.len() - 1], 0.000_001),
+        'T' => (&size[..size.len() - 1], 1000.0),
+        _ => (size, 1e-9),
+    };
+
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| n * scale)
+}
+
+fn format_billions(billions: f64) -> String {
+    if billions < 1.0 {
+        format!("{:.0}M", billions * 1000.0)
+    } else {
+        format!("{:.1}B", billions)
+    }
+}
@@ -0,0 +1,214 @@
+use crate::database::models::ProjectExport;
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactOptions {
+    /// Match terms anywhere, including inside longer words. Whole words only by default.
+    pub substring: bool,
+    /// Treat terms as regular expressions rather than literal text.
+    pub regex: bool,
+}
+
+/// Replaces each term with a numbered placeholder (`[REDACTED-1]`, ...). Matching is
+/// case-insensitive, and the same term always gets the same placeholder. All terms are matched
+/// in a single pass, so a placeholder is never redacted again by a later term.
+pub struct Redactor {
+    regex: Regex,
+    /// Capture group name and placeholder of each term, in the order the terms were given.
+    terms: Vec<(String, String)>,
+}
+
+impl Redactor {
+    pub fn new(terms: &[String], options: &RedactOptions) -> Result<Self, String> {
+        let terms: Vec<&str> = terms
+            .iter()
+            .map(|term| term.trim())
+            .filter(|term| !term.is_empty())
+            .collect();
+        if terms.is_empty() {
+            return Err("At least one term to redact is required".to_string());
+        }
+
+        let mut alternatives = Vec::new();
+        let mut groups = Vec::new();
+        for (i, term) in terms.into_iter().enumerate() {
+            let pattern = pattern_for(term, options);
+            // Each term is checked on its own first, so the error names the term at fault.
+            build(&pattern).map_err(|e| format!("Invalid redaction pattern {:?}: {}", term, e))?;
+
+            let group = format!("redact{}", i);
+            alternatives.push(format!("(?P<{}>{})", group, pattern));
+            groups.push((group, format!("[REDACTED-{}]", i + 1)));
+        }
+        let regex = build(&alternatives.join("|")).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            regex,
+            terms: groups,
+        })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.regex
+            .replace_all(text, |captures: &Captures| {
+                self.terms
+                    .iter()
+                    .find(|(group, _)| captures.name(group).is_some())
+                    .map_or("", |(_, placeholder)| placeholder.as_str())
+                    .to_string()
+            })
+            .into_owned()
+    }
+
+    /// Redacts every user-written field of the export: project details, requirements,
+    /// conversation titles and rolling summaries, message content and the string values inside
+    /// message metadata.
+    pub fn redact_export(&self, mut export: ProjectExport) -> ProjectExport {
+        let project = &mut export.project;
+        project.name = self.apply(&project.name);
+        project.description = self.apply(&project.description);
+        project.industry = project.industry.as_deref().map(|v| self.apply(v));
+        project.target_audience = project.target_audience.as_deref().map(|v| self.apply(v));
+        project.persona = project.persona.as_deref().map(|v| self.apply(v));
+
+        for requirement in &mut export.requirements {
+            requirement.text = self.apply(&requirement.text);
+        }
+
+        for entry in &mut export.conversations {
+            let conversation = &mut entry.conversation;
+            conversation.title = conversation.title.as_deref().map(|v| self.apply(v));
+            entry.rolling_summary = entry.rolling_summary.as_deref().map(|v| self.apply(v));
+
+            for message in &mut entry.messages {
+                message.content = self.apply(&message.content);
+                message.metadata = message.metadata.as_deref().map(|v| self.redact_json(v));
+            }
+        }
+
+        export
+    }
+
+    /// Redacts string values only, so keys and structure stay valid JSON. Text that does not
+    /// parse is redacted as a whole.
+    fn redact_json(&self, json: &str) -> String {
+        match serde_json::from_str::<Value>(json) {
+            Ok(mut value) => {
+                self.redact_value(&mut value);
+                value.to_string()
+            }
+            Err(_) => self.apply(json),
+        }
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.apply(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::Object(map) => map.values_mut().for_each(|item| self.redact_value(item)),
+            _ => {}
+        }
+    }
+}
+
+fn build(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+fn pattern_for(term: &str, options: &RedactOptions) -> String {
+    if options.regex {
+        return if options.substring {
+            term.to_string()
+        } else {
+            format!(r"\b(?:{})\b", term)
+        };
+    }
+
+    let escaped = regex::escape(term);
+    if options.substring {
+        return escaped;
+    }
+
+    // `\b` only matches next to a word character, so a term like "C++" gets a boundary on the
+    // side that has one.
+    let boundary = |c: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        boundary(term.chars().next()),
+        escaped,
+        boundary(term.chars().last())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> ProjectExport {
+        serde_json::from_value(serde_json::json!({
+            "project": {
+                "id": "p1",
+                "name": "Acme portal",
+                "description": "Customer portal for Acme",
+                "industry": null,
+                "target_audience": "Acme staff",
+                "status": "active",
+                "response_format": "markdown",
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z",
+                "persona": "A consultant who knows Acme well"
+            },
+            "requirements": [],
+            "conversations": [{
+                "conversation": {
+                    "id": "c1",
+                    "project_id": "p1",
+                    "phase": "consultation",
+                    "auto_advance": false,
+                    "created_at": "2024-01-01T00:00:00Z",
+                    "title": "Acme kickoff"
+                },
+                "rolling_summary": "Acme wants a portal",
+                "messages": [{
+                    "id": "m1",
+                    "conversation_id": "c1",
+                    "role": "user",
+                    "content": "Acme needs single sign-on",
+                    "metadata": "{\"note\":\"from Acme\"}",
+                    "created_at": "2024-01-01T00:00:00Z"
+                }]
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn redacts_every_user_written_field() {
+        let redactor = Redactor::new(&["acme".to_string()], &RedactOptions::default()).unwrap();
+        let export = redactor.redact_export(export());
+
+        let rendered = serde_json::to_string(&export).unwrap();
+        assert!(!rendered.to_lowercase().contains("acme"), "{}", rendered);
+        assert_eq!(
+            export.conversations[0].conversation.title.as_deref(),
+            Some("[REDACTED-1] kickoff")
+        );
+    }
+
+    #[test]
+    fn placeholders_are_not_redacted_again() {
+        let terms = ["acme", "redacted", "1"].map(String::from);
+        let redactor = Redactor::new(&terms, &RedactOptions::default()).unwrap();
+
+        assert_eq!(
+            redactor.apply("Acme has 1 redacted file"),
+            "[REDACTED-1] has [REDACTED-3] [REDACTED-2] file"
+        );
+    }
+}
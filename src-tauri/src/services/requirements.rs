@@ -0,0 +1,42 @@
+use serde::Deserialize;
+
+pub const PRIORITIES: [&str; 3] = ["high", "medium", "low"];
+pub const STATUSES: [&str; 4] = ["proposed", "accepted", "done", "rejected"];
+
+pub const EXTRACTION_PROMPT: &str = "Extract the individual product requirements agreed or \
+proposed in the following specification discussion. Each requirement should be a single, \
+testable statement. Reply with ONLY a JSON array of the form \
+[{\"text\": \"requirement\", \"priority\": \"high\" | \"medium\" | \"low\"}].";
+
+#[derive(Debug, Deserialize)]
+pub struct ExtractedRequirement {
+    pub text: String,
+    #[serde(default)]
+    pub priority: Option<String>,
+}
+
+/// Dedup key for requirements: case-insensitive, whitespace-collapsed, trailing punctuation
+/// ignored.
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['.', '!', ';', ','])
+        .to_lowercase()
+}
+
+pub fn validate_priority(priority: &str) -> Result<(), String> {
+    if PRIORITIES.contains(&priority) {
+        Ok(())
+    } else {
+        Err(format!("Unknown requirement priority: {}", priority))
+    }
+}
+
+pub fn validate_status(status: &str) -> Result<(), String> {
+    if STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("Unknown requirement status: {}", status))
+    }
+}
@@ -0,0 +1,22 @@
+use serde::Deserialize;
+
+pub const COMPLETENESS_PROMPT: &str = "You are reviewing a product specification discussion. \
+Assess which of these sections the discussion adequately covers: problem statement, target users, \
+goals and success metrics, functional requirements, non-functional requirements, scope and \
+non-goals, user flows, technical constraints, risks and open questions. \
+Reply with JSON of the form {\"covered\": [\"section\", ...], \"missing\": [\"section\", ...]}.";
+
+pub const COMPLETENESS_RETRY_PROMPT: &str = "Your previous reply could not be parsed. \
+Classify each of these sections as covered or missing for the discussion that follows: problem \
+statement, target users, goals and success metrics, functional requirements, non-functional \
+requirements, scope and non-goals, user flows, technical constraints, risks and open questions. \
+Reply with ONLY a JSON object, no prose and no code fences, exactly of the form \
+{\"covered\": [\"section\"], \"missing\": [\"section\"]}.";
+
+#[derive(Debug, Deserialize)]
+pub struct CompletenessSections {
+    #[serde(default)]
+    pub covered: Vec<String>,
+    #[serde(default)]
+    pub missing: Vec<String>,
+}
@@ -0,0 +1,58 @@
+//! A distilled project state each conversation keeps up to date and sends ahead of its history,
+//! so the model keeps track of decisions without rereading everything.
+
+use super::ollama::ChatMessage;
+use super::prompt;
+
+/// User turns between updates when the `rolling_summary_interval` setting is unset.
+pub const DEFAULT_INTERVAL: u32 = 6;
+
+/// Messages asking the model to fold `transcript` into the `existing` summary.
+pub fn update_request(
+    existing: Option<&str>,
+    transcript: &str,
+    language: Option<&str>,
+) -> Vec<ChatMessage> {
+    let mut system_prompt = "You maintain a concise running summary of a product specification \
+                             discussion. Merge the new exchanges into the current summary: keep \
+                             every decision, requirement, constraint and open question, update \
+                             anything the new exchanges changed, and drop small talk. Reply with \
+                             ONLY the updated summary."
+        .to_string();
+    if let Some(language) = language {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(&prompt::language_instruction(language));
+    }
+
+    let content = format!(
+        "Current summary:\n\n{}\n\nNew exchanges:\n\n{}",
+        existing.unwrap_or("(none yet)"),
+        transcript
+    );
+
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+            images: Vec::new(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content,
+            images: Vec::new(),
+        },
+    ]
+}
+
+/// The summary as sent right after the phase prompt, ahead of the history.
+pub fn context_message(summary: &str) -> ChatMessage {
+    ChatMessage {
+        role: "system".to_string(),
+        content: format!(
+            "Project state so far, kept up to date as the conversation goes on. Treat it as \
+             settled unless the user changes it:\n\n{}",
+            summary.trim()
+        ),
+        images: Vec::new(),
+    }
+}
@@ -0,0 +1,86 @@
+//! Neutralizes control sequences in message text before it is sent to the model. Stored
+//! content is never changed; only the outgoing copy is.
+//!
+//! Handled patterns, each broken up with a zero-width space after its first character so it
+//! reads the same but no longer tokenizes as a control token:
+//! - special tokens of the `<|name|>` form (ChatML `<|im_start|>`, Llama 3
+//!   `<|start_header_id|>` and `<|eot_id|>`, `<|endoftext|>`, ...)
+//! - Llama 2 and Mistral delimiters: `[INST]`, `[/INST]`, `<<SYS>>`, `<</SYS>>`, `<s>`, `</s>`
+//! - the workflow's own phase completion marker, so pasted text cannot end a phase
+//!
+//! Messages longer than `MAX_MESSAGE_CHARS` are cut, with a note saying how much was left out.
+
+use super::workflow::PHASE_COMPLETE_MARKER;
+use regex::Regex;
+use std::sync::LazyLock;
+
+pub const MAX_MESSAGE_CHARS: usize = 32_000;
+
+static CONTROL_SEQUENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"<\|[A-Za-z0-9_]{{1,64}}\|>|\[/?INST\]|<</?SYS>>|</?s>|{}",
+        regex::escape(PHASE_COMPLETE_MARKER)
+    ))
+    .expect("control sequence pattern is valid")
+});
+
+pub fn sanitize(content: &str) -> String {
+    let content = CONTROL_SEQUENCE.replace_all(content, |caps: &regex::Captures| {
+        let sequence = &caps[0];
+        format!("{}\u{200B}{}", &sequence[..1], &sequence[1..])
+    });
+
+    match content.char_indices().nth(MAX_MESSAGE_CHARS) {
+        Some((cut, _)) => format!(
+            "{}\n\n[{} more characters omitted]",
+            &content[..cut],
+            content[cut..].chars().count()
+        ),
+        None => content.into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_sequences_are_broken_up() {
+        for (input, expected) in [
+            ("<|im_start|>system", "<\u{200B}|im_start|>system"),
+            ("<|eot_id|>", "<\u{200B}|eot_id|>"),
+            ("[INST] hi [/INST]", "[\u{200B}INST] hi [\u{200B}/INST]"),
+            ("<<SYS>>x<</SYS>>", "<\u{200B}<SYS>>x<\u{200B}</SYS>>"),
+            ("<s>text</s>", "<\u{200B}s>text<\u{200B}/s>"),
+            ("done [[PHASE_COMPLETE]]", "done [\u{200B}[PHASE_COMPLETE]]"),
+        ] {
+            assert_eq!(sanitize(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn ordinary_text_is_left_alone() {
+        for input in [
+            "a | b | c",
+            "Use <strong> and <span> tags",
+            "Vec<|x| x + 1> is not a token",
+            "[install] the package",
+            "<|not a token|>",
+        ] {
+            assert_eq!(sanitize(input), input);
+        }
+    }
+
+    #[test]
+    fn long_messages_are_cut_with_a_note() {
+        let long = "é".repeat(MAX_MESSAGE_CHARS + 5);
+
+        let sanitized = sanitize(&long);
+        assert!(sanitized.starts_with(&"é".repeat(MAX_MESSAGE_CHARS)));
+        assert!(sanitized.ends_with("\n\n[5 more characters omitted]"));
+        assert_eq!(
+            sanitize(&long[..MAX_MESSAGE_CHARS * 2]),
+            &long[..MAX_MESSAGE_CHARS * 2]
+        );
+    }
+}
@@ -0,0 +1,24 @@
+use serde::de::DeserializeOwned;
+
+/// Parses a JSON value out of a model reply, tolerating surrounding prose and code fences by
+/// taking the outermost object or array in the text.
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    if let Ok(value) = serde_json::from_str(text.trim()) {
+        return Ok(value);
+    }
+
+    let start = text
+        .find(['{', '['])
+        .ok_or_else(|| "Response did not contain JSON".to_string())?;
+    let closing = if text[start..].starts_with('{') {
+        '}'
+    } else {
+        ']'
+    };
+    let end = text
+        .rfind(closing)
+        .filter(|end| *end > start)
+        .ok_or_else(|| "Response contained unterminated JSON".to_string())?;
+
+    serde_json::from_str(&text[start..=end]).map_err(|e| e.to_string())
+}
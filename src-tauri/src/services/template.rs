@@ -0,0 +1,82 @@
+//! `{{name}}` placeholder substitution shared by conversation variables and prompt templates.
+//!
+//! Names may contain ASCII letters, digits, `_`, `-` and `.`, with optional whitespace inside
+//! the braces. Placeholders with no value are left untouched. To write a literal `{{name}}`,
+//! escape the opening braces with a backslash: `\{{name}}` renders as `{{name}}`.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplatePreview {
+    pub rendered: String,
+    /// Placeholders with no value, each once, in order of first use.
+    pub unresolved: Vec<String>,
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+pub fn render<F>(text: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    preview(text, lookup).rendered
+}
+
+/// Renders `text` like `render`, also reporting which placeholders were left untouched.
+pub fn preview<F>(text: &str, lookup: F) -> TemplatePreview
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut unresolved: Vec<String> = Vec::new();
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        if rest[..start].ends_with('\\') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        let Some(end) = after.find("}}") else {
+            output.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+
+        let name = after[..end].trim();
+        if !is_valid_name(name) {
+            output.push_str("{{");
+            rest = after;
+            continue;
+        }
+
+        match lookup(name) {
+            Some(value) => output.push_str(&value),
+            None => {
+                output.push_str(&rest[start..start + 2 + end + 2]);
+                if !unresolved.iter().any(|known| known == name) {
+                    unresolved.push(name.to_string());
+                }
+            }
+        }
+
+        rest = &after[end + 2..];
+    }
+
+    output.push_str(rest);
+
+    TemplatePreview {
+        rendered: output,
+        unresolved,
+    }
+}
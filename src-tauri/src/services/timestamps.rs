@@ -0,0 +1,145 @@
+//! Normalization of stored timestamps to the RFC 3339 UTC form the app writes, so that
+//! `created_at` ordering holds for rows that came from elsewhere.
+//!
+//! Accepted inputs are RFC 3339 with any offset, RFC 2822, SQLite's `YYYY-MM-DD HH:MM:SS`
+//! (read as UTC), bare dates, and Unix timestamps in seconds or milliseconds. Times after
+//! "now" are clamped to it.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+/// Unix timestamps above this are taken to be in milliseconds (it is in the year 5138 as
+/// seconds).
+const UNIX_MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+/// Normalizes timestamps against a fixed "now" and counts how many it had to change.
+pub struct TimestampFixer {
+    now: DateTime<Utc>,
+    pub fixed: usize,
+}
+
+impl Default for TimestampFixer {
+    fn default() -> Self {
+        Self {
+            now: Utc::now(),
+            fixed: 0,
+        }
+    }
+}
+
+impl TimestampFixer {
+    pub fn fix(&mut self, value: &str) -> Result<String, String> {
+        let normalized = normalize(value, self.now)?;
+        if normalized != value {
+            self.fixed += 1;
+        }
+        Ok(normalized)
+    }
+}
+
+pub fn normalize(value: &str, now: DateTime<Utc>) -> Result<String, String> {
+    let parsed = parse(value.trim()).ok_or_else(|| format!("Invalid timestamp: {:?}", value))?;
+    Ok(parsed.min(now).to_rfc3339())
+}
+
+fn parse(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(value) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    if let Ok(parsed) = DateTime::parse_from_rfc2822(value) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(parsed) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(parsed.and_utc());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+
+    let unix: i64 = value.parse().ok()?;
+    if unix.abs() >= UNIX_MILLIS_THRESHOLD {
+        DateTime::from_timestamp_millis(unix)
+    } else {
+        DateTime::from_timestamp(unix, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn offsets_are_converted_to_utc() {
+        assert_eq!(
+            normalize("2024-05-01T10:30:00+02:00", now()).unwrap(),
+            "2024-05-01T08:30:00+00:00"
+        );
+        assert_eq!(
+            normalize("Wed, 01 May 2024 10:30:00 -0500", now()).unwrap(),
+            "2024-05-01T15:30:00+00:00"
+        );
+    }
+
+    #[test]
+    fn other_known_forms_are_read_as_utc() {
+        for value in [
+            "2024-05-01 08:30:00",
+            " 2024-05-01T08:30:00 ",
+            "1714552200",
+            "1714552200000",
+        ] {
+            assert_eq!(
+                normalize(value, now()).unwrap(),
+                "2024-05-01T08:30:00+00:00",
+                "{}",
+                value
+            );
+        }
+        assert_eq!(
+            normalize("2024-05-01", now()).unwrap(),
+            "2024-05-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn future_times_are_clamped_to_now() {
+        assert_eq!(
+            normalize("2999-01-01T00:00:00Z", now()).unwrap(),
+            now().to_rfc3339()
+        );
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        for value in [
+            "",
+            "yesterday",
+            "2024-13-01",
+            "2024-05-01T25:00:00Z",
+            "12:30",
+        ] {
+            assert!(normalize(value, now()).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn the_fixer_counts_only_changed_values() {
+        let mut fixer = TimestampFixer {
+            now: now(),
+            fixed: 0,
+        };
+
+        fixer.fix("2024-05-01T08:30:00+00:00").unwrap();
+        fixer.fix("2024-05-01 08:30:00").unwrap();
+        assert!(fixer.fix("never").is_err());
+        assert_eq!(fixer.fixed, 1);
+    }
+}
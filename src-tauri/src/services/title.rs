@@ -0,0 +1,42 @@
+//! Automatic titles for conversations started without one.
+
+use super::ollama::ChatMessage;
+
+/// Messages asking for a title summing up the opening exchange.
+pub fn request(first_message: &str, first_reply: &str) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: "Write a short title, at most six words, for the product specification \
+                      conversation below. Reply with ONLY the title, without quotes or trailing \
+                      punctuation."
+                .to_string(),
+            images: Vec::new(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!("user: {}\n\nassistant: {}", first_message, first_reply),
+            images: Vec::new(),
+        },
+    ]
+}
+
+/// The first non-empty line of the reply, stripped of quotes, markdown emphasis and trailing
+/// punctuation and cut to `max_chars`. `None` if nothing usable is left.
+pub fn clean(response: &str, max_chars: usize) -> Option<String> {
+    let line = response
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+    let line = line
+        .strip_prefix("Title:")
+        .unwrap_or(line)
+        .trim()
+        .trim_matches(['"', '\'', '*', '#', '`'])
+        .trim_end_matches(['.', '!', ':'])
+        .trim();
+
+    let title: String = line.chars().take(max_chars).collect();
+    let title = title.trim_end().to_string();
+    (!title.is_empty()).then_some(title)
+}
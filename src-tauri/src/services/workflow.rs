@@ -0,0 +1,56 @@
+/// Ordered phases of the guided spec workflow, matching the `conversations.phase` CHECK constraint.
+pub const PHASES: [&str; 5] = [
+    "initial_analysis",
+    "consultation",
+    "context_building",
+    "generation",
+    "refinement",
+];
+
+/// Marker the model emits on its own line once the current phase's goals are met.
+pub const PHASE_COMPLETE_MARKER: &str = "[[PHASE_COMPLETE]]";
+
+pub fn next_phase(phase: &str) -> Option<&'static str> {
+    let index = PHASES.iter().position(|p| *p == phase)?;
+    PHASES.get(index + 1).copied()
+}
+
+pub fn phase_instruction(phase: &str) -> String {
+    format!(
+        "You are guiding the user through the '{}' phase of writing a product specification. \
+         When this phase's goals have been fully covered, end your reply with the line {} on its own. \
+         Do not emit the marker otherwise.",
+        phase, PHASE_COMPLETE_MARKER
+    )
+}
+
+/// Strips standalone completion markers from the response, returning the cleaned content and
+/// whether a marker was found. Markers inside fenced code blocks or inline code are left alone.
+pub fn extract_phase_marker(content: &str) -> (String, bool) {
+    let mut in_fence = false;
+    let mut found = false;
+    let mut lines = Vec::new();
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            lines.push(line);
+            continue;
+        }
+
+        if !in_fence && trimmed.eq_ignore_ascii_case(PHASE_COMPLETE_MARKER) {
+            found = true;
+            continue;
+        }
+
+        lines.push(line);
+    }
+
+    if !found {
+        return (content.to_string(), false);
+    }
+
+    (lines.join("\n").trim_end().to_string(), true)
+}
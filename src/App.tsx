@@ -8,6 +8,7 @@ import {
   Conversation,
   Message,
   CreateMessageInput,
+  SendMessageResponse,
 } from "./types";
 
 function App() {
@@ -79,6 +80,7 @@ function App() {
       conversation_id: conversation.id,
       role: "user",
       content: input.content,
+      selected: true,
       created_at: new Date().toISOString(),
     };
 
@@ -86,10 +88,12 @@ function App() {
     setLoading(true);
 
     try {
-      const assistantMessage = await invoke<Message>("send_message", {
-        input,
-      });
+      const { warning, ...assistantMessage } =
+        await invoke<SendMessageResponse>("send_message", { input });
       setMessages((prev) => [...prev, assistantMessage]);
+      if (warning) {
+        console.warn(warning);
+      }
     } catch (error) {
       console.error("Failed to send message:", error);
     } finally {
@@ -1,9 +1,18 @@
 import { useState, useEffect, useRef } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { Button } from "./ui/button";
 import { Textarea } from "./ui/textarea";
-import { Message, CreateMessageInput, Conversation, Project } from "../types";
+import {
+  Message,
+  CreateMessageInput,
+  Conversation,
+  Project,
+  Draft,
+} from "../types";
 import { Send, Bot, User } from "lucide-react";
 
+const DRAFT_SAVE_DELAY_MS = 500;
+
 interface ChatViewProps {
   project: Project;
   conversation: Conversation | null;
@@ -22,12 +31,50 @@ export function ChatView({
   loading,
 }: ChatViewProps) {
   const [input, setInput] = useState("");
+  // The conversation whose draft `input` holds. Until it matches, `input` may still be the
+  // previous conversation's text, so nothing is saved.
+  const [draftConversationId, setDraftConversationId] = useState<
+    string | null
+  >(null);
   const messagesEndRef = useRef<HTMLDivElement>(null);
 
   useEffect(() => {
     messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
   }, [messages]);
 
+  useEffect(() => {
+    setDraftConversationId(null);
+    if (!conversation) return;
+
+    const conversationId = conversation.id;
+    let stale = false;
+    invoke<Draft | null>("get_draft", { conversationId })
+      .then((draft) => {
+        if (stale) return;
+        setInput(draft?.content ?? "");
+        setDraftConversationId(conversationId);
+      })
+      .catch((error) => console.error("Failed to load draft:", error));
+
+    return () => {
+      stale = true;
+    };
+  }, [conversation?.id]);
+
+  useEffect(() => {
+    if (!draftConversationId || draftConversationId !== conversation?.id) return;
+
+    const timeout = setTimeout(() => {
+      const command = input.trim() ? "save_draft" : "clear_draft";
+      invoke(command, {
+        conversationId: draftConversationId,
+        content: input,
+      }).catch((error) => console.error("Failed to save draft:", error));
+    }, DRAFT_SAVE_DELAY_MS);
+
+    return () => clearTimeout(timeout);
+  }, [input, draftConversationId, conversation?.id]);
+
   useEffect(() => {
     if (project && !conversation && !loading) {
       onCreateConversation(project.id);
@@ -5,8 +5,12 @@ export interface Project {
   industry?: string;
   target_audience?: string;
   status: string;
+  response_format: string;
+  language?: string;
+  readonly: boolean;
   created_at: string;
   updated_at: string;
+  persona?: string;
 }
 
 export interface CreateProjectInput {
@@ -20,7 +24,23 @@ export interface Conversation {
   id: string;
   project_id: string;
   phase: string;
+  auto_advance: boolean;
+  response_format?: string;
+  language?: string;
+  source_conversation_id?: string;
+  max_output_tokens?: number;
+  ollama_version?: string;
+  created_with_model?: string;
   created_at: string;
+  updated_at: string;
+  title?: string;
+  title_locked: boolean;
+  archived: boolean;
+}
+
+export interface RecentConversation extends Conversation {
+  project_name: string;
+  last_message?: string;
 }
 
 export interface Message {
@@ -29,12 +49,67 @@ export interface Message {
   role: string;
   content: string;
   metadata?: string;
+  phase?: string;
+  variant_group?: string;
+  selected: boolean;
+  pinned: boolean;
   created_at: string;
 }
 
+export interface SendMessageResponse extends Message {
+  warning?: string;
+  sources?: string[];
+  variants?: Message[];
+  conversation?: Conversation;
+}
+
+export interface Draft {
+  conversation_id: string;
+  content: string;
+  updated_at: string;
+}
+
 export interface CreateMessageInput {
   conversation_id: string;
   role: string;
   content: string;
   metadata?: string;
+  model?: string;
+  cite_sources?: boolean;
+  n?: number;
+  project_id?: string;
+  allow_empty?: boolean;
+}
+
+export interface ModelAlias {
+  alias: string;
+  model: string;
+  updated_at: string;
+}
+
+export type ConnectionStatus =
+  | 'reachable'
+  | 'connection_refused'
+  | 'timeout'
+  | 'unauthorized'
+  | 'wrong_service';
+
+export interface Capabilities {
+  backend: string;
+  connection: ConnectionStatus;
+  version?: string;
+  streaming: boolean;
+  streaming_enabled: boolean;
+  embeddings: boolean;
+}
+
+export type OnboardingStep = 'connect_ollama' | 'pull_model' | 'choose_model';
+
+export interface OnboardingStatus {
+  connection: ConnectionStatus;
+  installed_models?: string[];
+  configured_model: string;
+  configured_model_installed?: boolean;
+  completed_onboarding: boolean;
+  next_step?: OnboardingStep;
 }